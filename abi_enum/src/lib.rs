@@ -0,0 +1,54 @@
+#![no_std]
+
+/// Define a fieldless enum which is passed between kernel and user as an integer.
+/// TryFrom from the integer type and VARIANTS (every variant in declaration order)
+/// are generated, so that a new variant can't be left out of the conversion.
+///
+/// ```ignore
+/// abi_enum! {
+///     #[repr(u8)]
+///     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///     pub enum Color {
+///         Red = 1,
+///         Green,
+///     }
+///     impl TryFrom<u8, Error = ()> => ();
+/// }
+/// ```
+#[macro_export]
+macro_rules! abi_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident $(= $value:expr)?
+            ),* $(,)?
+        }
+        impl TryFrom<$int:ty, Error = $error_ty:ty> => $error:expr;
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant $(= $value)?,
+            )*
+        }
+
+        impl $name {
+            pub const VARIANTS: &'static [Self] = &[$(Self::$variant),*];
+        }
+
+        impl TryFrom<$int> for $name {
+            type Error = $error_ty;
+            fn try_from(value: $int) -> Result<Self, Self::Error> {
+                $(
+                    if value == Self::$variant as $int {
+                        return Ok(Self::$variant);
+                    }
+                )*
+                Err($error)
+            }
+        }
+    };
+}
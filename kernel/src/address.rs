@@ -1,5 +1,26 @@
 pub const PAGE_SIZE: usize = 4096;
-pub const KERNEL_V_ADDR_PFX: usize = 0xffff800000000000;
+
+#[cfg(any(
+    all(feature = "sv39", feature = "sv48"),
+    all(feature = "sv39", feature = "sv57"),
+    all(feature = "sv48", feature = "sv57"),
+))]
+compile_error!("only one of features \"sv39\", \"sv48\" and \"sv57\" can be enabled");
+
+/// Number of page table levels, sv48 is used when neither sv39 nor sv57 is enabled.
+#[cfg(feature = "sv39")]
+pub const PAGE_LEVELS: usize = 3;
+#[cfg(feature = "sv57")]
+pub const PAGE_LEVELS: usize = 5;
+#[cfg(not(any(feature = "sv39", feature = "sv57")))]
+pub const PAGE_LEVELS: usize = 4;
+
+pub const VADDR_BITS: usize = 12 + 9 * PAGE_LEVELS;
+/// Lower half of virtual address space is for user.
+pub const USER_VADDR_LIMIT: usize = 1 << (VADDR_BITS - 1);
+/// Start of upper half, where all physical memory is mapped.
+/// sv39: 0xffffffc000000000, sv48: 0xffff800000000000, sv57: 0xff00000000000000
+pub const KERNEL_V_ADDR_PFX: usize = !(USER_VADDR_LIMIT - 1);
 
 mod inner {
     use core::{fmt, marker::PhantomData, ops};
@@ -11,10 +11,13 @@ use shared::const_assert;
 use core::mem;
 use core::{marker::PhantomData, num::NonZeroU8};
 
+pub mod asid_pool;
+pub mod benchmark;
 pub mod cnode;
 pub mod endpoint;
 pub mod notification;
 pub mod page_table;
+pub mod sched_control;
 pub mod tcb;
 pub mod untyped;
 
@@ -76,10 +79,14 @@ impl CapInSlot {
         let cap_type = self.get_cap_type()?;
         (CapabilityData::<NK>::CAP_TYPE == cap_type)
             .then_some(())
-            .ok_or(kerr!(ErrKind::UnexpectedCapType))?;
+            .ok_or_else(|| {
+                kerr!(ErrKind::UnexpectedCapType)
+                    .with_cap_type(CapabilityData::<NK>::CAP_TYPE, cap_type)
+            })?;
         unsafe { Ok(self.unchecked_ref_mut_as()) }
     }
 
+    #[allow(dead_code)]
     pub fn try_ref_as<NK>(&self) -> KernelResult<&CapabilityData<NK>>
     where
         NK: KObject,
@@ -88,7 +95,10 @@ impl CapInSlot {
         let cap_type = self.get_cap_type()?;
         (CapabilityData::<NK>::CAP_TYPE == cap_type)
             .then_some(())
-            .ok_or(kerr!(ErrKind::UnexpectedCapType))?;
+            .ok_or_else(|| {
+                kerr!(ErrKind::UnexpectedCapType)
+                    .with_cap_type(CapabilityData::<NK>::CAP_TYPE, cap_type)
+            })?;
         unsafe { Ok(self.unchecked_ref_as()) }
     }
 
@@ -183,6 +193,12 @@ where
     fn get_object_size(_user_size: usize) -> usize {
         mem::size_of::<Self::KernelObject>()
     }
+    fn get_object_align(_user_size: usize) -> usize {
+        mem::align_of::<Self::KernelObject>()
+    }
+    fn check_user_size(_user_size: usize) -> KernelResult<()> {
+        Ok(())
+    }
     fn can_be_retyped_from_device_memory() -> bool {
         false
     }
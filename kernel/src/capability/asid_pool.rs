@@ -0,0 +1,102 @@
+use super::untyped::UntypedCap;
+use super::{Capability, CapabilityData, CapabilityType, Something};
+use crate::address::KernelVAddress;
+use crate::common::{ErrKind, KernelResult};
+use crate::kerr;
+use crate::object::asid_pool::{find_free_asid_base, register_asid_pool, ASID_BITS};
+use crate::object::{AsidControl, AsidPool, CNodeEntry, CSlot, KObject, PageTable, Untyped};
+
+/*
+ * AsidControl
+ * RawCapability[0]
+ * |                            padding                             |
+ * 64                                                               0
+ */
+
+impl KObject for AsidControl {}
+
+pub type AsidControlCap = CapabilityData<AsidControl>;
+
+impl Capability for AsidControlCap {
+    const CAP_TYPE: CapabilityType = CapabilityType::AsidControl;
+    type KernelObject = AsidControl;
+
+    fn derive(&self, _src_slot: &CNodeEntry<Something>) -> KernelResult<Self> {
+        Ok(self.replicate())
+    }
+
+    fn init_object(&mut self) {}
+}
+
+impl AsidControlCap {
+    /// Create new AsidPool from untyped memory and register it into ASID_TABLE.
+    pub fn make_pool(
+        &mut self,
+        untyped_entry: &mut CNodeEntry<Untyped>,
+        dest_slot: &mut CSlot,
+    ) -> KernelResult<()> {
+        dest_slot
+            .is_none()
+            .then_some(())
+            .ok_or(kerr!(ErrKind::NotEmptySlot))?;
+        let asid_base = find_free_asid_base()?;
+        let untyped: &mut UntypedCap = untyped_entry.cap_ref_mut();
+        let mut pool_cap = untyped.retype::<AsidPool>(0, 1)?.next().unwrap();
+        pool_cap.init_object();
+        pool_cap.cap_dep_val = asid_base as u64;
+        register_asid_pool(asid_base, pool_cap.get_pool())?;
+        let entry = dest_slot.insert(CNodeEntry::new_with_rawcap(pool_cap.into()));
+        entry.insert(untyped_entry);
+        Ok(())
+    }
+}
+
+/*
+ * AsidPool
+ * RawCapability[0]
+ * |          padding 50          |          asid base 14          |
+ * 64                                                              0
+ */
+
+impl KObject for AsidPool {}
+
+pub type AsidPoolCap = CapabilityData<AsidPool>;
+
+impl Capability for AsidPoolCap {
+    const CAP_TYPE: CapabilityType = CapabilityType::AsidPool;
+    type KernelObject = AsidPool;
+
+    fn derive(&self, _src_slot: &CNodeEntry<Something>) -> KernelResult<Self> {
+        Ok(self.replicate())
+    }
+
+    fn init_object(&mut self) {
+        let ptr: *mut AsidPool = KernelVAddress::from(self.get_address()).into();
+        unsafe {
+            *ptr = AsidPool::new();
+        }
+    }
+}
+
+impl AsidPoolCap {
+    pub fn init_with_base(address: KernelVAddress, asid_base: usize) -> Self {
+        let mut cap = Self::init(address, 0);
+        cap.cap_dep_val = asid_base as u64;
+        cap
+    }
+
+    /// Assign unused asid in this pool to given root page table.
+    pub fn assign(&mut self, table: &mut PageTable) -> KernelResult<usize> {
+        let asid_base = self.asid_base();
+        self.get_pool().assign(asid_base, table)
+    }
+
+    pub fn get_pool(&mut self) -> &mut AsidPool {
+        let ptr: *mut AsidPool = KernelVAddress::from(self.get_address()).into();
+        unsafe { ptr.as_mut().unwrap() }
+    }
+
+    fn asid_base(&self) -> usize {
+        (self.cap_dep_val as usize) & ((1 << ASID_BITS) - 1)
+    }
+}
@@ -0,0 +1,49 @@
+use super::{Capability, CapabilityData, CapabilityType, Something};
+use crate::common::KernelResult;
+use crate::object::{Benchmark, CNodeEntry, KObject};
+use crate::pmu::{config_counter, read_counter, release_counter, start_counter, stop_counter};
+use shared::types::CounterValue;
+
+/*
+ * Benchmark
+ * RawCapability[0]
+ * |                            padding                             |
+ * 64                                                               0
+ */
+
+impl KObject for Benchmark {}
+
+pub type BenchmarkCap = CapabilityData<Benchmark>;
+
+impl Capability for BenchmarkCap {
+    const CAP_TYPE: CapabilityType = CapabilityType::Benchmark;
+    type KernelObject = Benchmark;
+
+    fn derive(&self, _src_slot: &CNodeEntry<Something>) -> KernelResult<Self> {
+        Ok(self.replicate())
+    }
+
+    fn init_object(&mut self) {}
+}
+
+impl BenchmarkCap {
+    pub fn config_counter(&self, event_idx: usize, event_data: usize) -> KernelResult<usize> {
+        config_counter(event_idx, event_data)
+    }
+
+    pub fn start_counter(&self, counter_idx: usize, initial_value: usize) -> KernelResult<()> {
+        start_counter(counter_idx, initial_value)
+    }
+
+    pub fn stop_counter(&self, counter_idx: usize) -> KernelResult<()> {
+        stop_counter(counter_idx)
+    }
+
+    pub fn read_counter(&self, counter_idx: usize) -> KernelResult<CounterValue> {
+        read_counter(counter_idx)
+    }
+
+    pub fn release_counter(&self, counter_idx: usize) -> KernelResult<()> {
+        release_counter(counter_idx)
+    }
+}
@@ -1,16 +1,24 @@
 use super::{Capability, CapabilityData, CapabilityType, Something};
 use crate::address::KernelVAddress;
 use crate::common::{ErrKind, KernelResult};
+use crate::kerr;
 use crate::object::{CNode, CNodeEntry, CSlot, KObject};
-use crate::{kerr, print, println};
+#[cfg(feature = "debug-syscalls")]
+use crate::{print, println};
+use shared::types::cnode_guard_from_value;
 
 use core::{mem, ptr};
 
 /*
  * RawCapability[0]
- * | padding |  radix  |
- * 63      32         0
+ * |  guard  | guard_size | radix |
+ * 63      12           6       0
  */
+const RADIX_BITS: u32 = 6;
+const GUARD_SIZE_BITS: u32 = 6;
+const GUARD_SHIFT: u32 = RADIX_BITS + GUARD_SIZE_BITS;
+// size of cnode in bytes must not overflow.
+const MAX_RADIX: u32 = usize::BITS - 1 - mem::size_of::<CNodeEntry<Something>>().ilog2();
 impl KObject for CNode {}
 
 pub type CNodeCap = CapabilityData<CNode>;
@@ -22,6 +30,12 @@ impl Capability for CNodeCap {
         user_size
     }
 
+    fn check_user_size(user_size: usize) -> KernelResult<()> {
+        (user_size <= MAX_RADIX as usize)
+            .then_some(())
+            .ok_or(kerr!(ErrKind::InvalidUserSize))
+    }
+
     fn get_object_size<'a>(user_size: usize) -> usize {
         2_usize.pow(user_size as u32) * mem::size_of::<CNodeEntry<Something>>()
     }
@@ -31,7 +45,7 @@ impl Capability for CNodeCap {
     }
 
     fn init_object(&mut self) {
-        // TODO: Zero clear
+        // memory is zero cleared on retype, and zeroed slot reads as None.
     }
 }
 
@@ -46,6 +60,7 @@ impl CNodeCap {
         unsafe { core::slice::from_raw_parts_mut(ptr.add(offset as usize), size) }
     }
 
+    #[cfg(feature = "debug-syscalls")]
     pub fn get_cnode_ref(&self) -> &[CSlot] {
         let ptr: KernelVAddress = self.get_address().into();
         let ptr: *const CSlot = ptr.into();
@@ -53,7 +68,7 @@ impl CNodeCap {
     }
 
     pub fn get_writable(&mut self, num: u32, index: u32) -> KernelResult<&mut CNode> {
-        (2_usize.pow(self.radix()) > num as usize + index as usize)
+        (2_usize.pow(self.radix()) >= num as usize + index as usize)
             .then_some(())
             .ok_or(kerr!(ErrKind::InvalidOperation))?;
         let cnode = self.get_cnode_with_offset_mut(index, num as usize);
@@ -85,33 +100,39 @@ impl CNodeCap {
         Ok((entry_1, entry_2))
     }
 
+    /// Resolve depth_bits of capptr from the most significant one.
+    /// At each level guard_size bits must match guard, and next radix bits are index of slot.
+    /// Lookup stops at non CNode cap even if some bits are left.
     pub fn lookup_entry_mut(&mut self, capptr: usize, depth_bits: u32) -> KernelResult<&mut CSlot> {
+        (depth_bits <= usize::BITS)
+            .then_some(())
+            .ok_or(kerr!(ErrKind::DepthMismatch).with_lookup(0, depth_bits))?;
         let mut cnode_cap = self;
-        let mut depth_bits = depth_bits;
+        let mut bits_left = depth_bits;
         loop {
-            let (next_cap, next_bits) = match cnode_cap._lookup_entry_mut(capptr, depth_bits)? {
-                (val @ &mut None, _) => return Ok(val),
-                (val, 0) => return Ok(val),
-                (val, rem) => {
-                    let cap_type = {
-                        let entry = val.as_mut().unwrap();
-                        entry.cap_ref().get_cap_type()?
-                    };
-                    if cap_type != CapabilityType::CNode {
-                        return Ok(val);
-                    }
-                    let entry = val.as_mut().unwrap();
-                    let cap = entry.cap_ref_mut().try_ref_mut_as().unwrap();
-                    (cap, rem)
+            let (val, rem) = cnode_cap
+                ._lookup_entry_mut(capptr, bits_left)
+                .map_err(|e| e.with_lookup(depth_bits - bits_left, bits_left))?;
+            if rem == 0 {
+                return Ok(val);
+            }
+            let cap_type = match val {
+                None => {
+                    return Err(kerr!(ErrKind::MissingCapability).with_lookup(depth_bits - rem, rem))
                 }
+                Some(entry) => entry.cap_ref().get_cap_type()?,
             };
-            cnode_cap = next_cap;
-            depth_bits = next_bits;
+            if cap_type != CapabilityType::CNode {
+                return Ok(val);
+            }
+            let entry = val.as_mut().unwrap();
+            cnode_cap = entry.cap_ref_mut().try_ref_mut_as().unwrap();
+            bits_left = rem;
         }
     }
 
     pub fn lookup_entry_mut_one_level(&mut self, capptr: usize) -> KernelResult<&mut CSlot> {
-        self.lookup_entry_mut(capptr, self.radix())
+        self.lookup_entry_mut(capptr, self.guard_size() + self.radix())
     }
 
     fn _lookup_entry_mut(
@@ -120,30 +141,67 @@ impl CNodeCap {
         depth_bits: u32,
     ) -> KernelResult<(&mut CSlot, u32)> {
         let radix = self.radix();
+        let guard_size = self.guard_size();
         let remain_bits = depth_bits
-            .checked_sub(radix)
-            .ok_or(kerr!(ErrKind::OutOfMemory))?;
+            .checked_sub(guard_size + radix)
+            .ok_or(kerr!(ErrKind::DepthMismatch))?;
+        let guard = capptr.checked_shr(remain_bits + radix).unwrap_or(0) & mask(guard_size);
+        (guard == self.guard())
+            .then_some(())
+            .ok_or(kerr!(ErrKind::GuardMismatch))?;
+        let offset = capptr.checked_shr(remain_bits).unwrap_or(0) & mask(radix);
         let cnode = self.get_cnode();
-        let offset = (capptr >> remain_bits) & ((1 << radix) - 1);
         let entry = &mut cnode[offset];
         Ok((entry, remain_bits))
     }
 
     fn radix(&self) -> u32 {
-        self.cap_dep_val as u32
+        (self.cap_dep_val as usize & mask(RADIX_BITS)) as u32
+    }
+
+    fn guard_size(&self) -> u32 {
+        ((self.cap_dep_val as usize >> RADIX_BITS) & mask(GUARD_SIZE_BITS)) as u32
+    }
+
+    fn guard(&self) -> usize {
+        self.cap_dep_val as usize >> GUARD_SHIFT
+    }
+
+    /// Set guard given by mint, see shared::types::cnode_guard.
+    pub fn set_guard(&mut self, value: usize) -> KernelResult<()> {
+        let (guard, guard_size) = cnode_guard_from_value(value);
+        (guard_size + self.radix() <= usize::BITS)
+            .then_some(())
+            .ok_or(kerr!(ErrKind::InvalidOperation))?;
+        (guard & !mask(guard_size) == 0 && guard & !mask(usize::BITS - GUARD_SHIFT) == 0)
+            .then_some(())
+            .ok_or(kerr!(ErrKind::InvalidOperation))?;
+        let radix = self.radix() as usize;
+        self.set_cap_dep_val(
+            (guard << GUARD_SHIFT) | ((guard_size as usize) << RADIX_BITS) | radix,
+        );
+        Ok(())
     }
     /// debug perpsoe
+    #[cfg(feature = "debug-syscalls")]
     pub fn print_traverse(&self) {
         self.print_level(0)
     }
 
+    #[cfg(feature = "debug-syscalls")]
     fn print_level(&self, level: usize) {
         let c_node = self.get_cnode_ref();
         for _ in 0..level {
             print!("  ");
         }
         print!("|");
-        println!("level is {}, radix is {}", level, self.radix());
+        println!(
+            "level is {}, radix is {}, guard is {:#x} ({} bits)",
+            level,
+            self.radix(),
+            self.guard(),
+            self.guard_size()
+        );
         for (i, slot) in c_node.iter().enumerate() {
             if let Some(ref entry) = slot {
                 for _ in 0..level {
@@ -165,3 +223,8 @@ impl CNodeCap {
         }
     }
 }
+
+/// Lower bits of usize, bits can be usize::BITS.
+fn mask(bits: u32) -> usize {
+    1_usize.checked_shl(bits).map_or(usize::MAX, |bit| bit - 1)
+}
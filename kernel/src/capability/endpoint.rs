@@ -58,8 +58,8 @@ impl EndPointCap {
         self.cap_dep_val
     }
 
-    #[allow(dead_code)]
-    pub fn set_badge(&mut self, _val: u64) -> KernelResult<()> {
-        todo!()
+    pub fn set_badge(&mut self, val: u64) -> KernelResult<()> {
+        self.cap_dep_val = val;
+        Ok(())
     }
 }
@@ -1,23 +1,37 @@
 use crate::address::PAGE_SIZE;
 use crate::common::ErrKind;
 use crate::kerr;
+use crate::object::asid_pool::{find_vspace_for_asid, ASID_BITS};
 use crate::object::page_table::Page;
 use crate::object::page_table::PageTable;
 use crate::object::KObject;
-use crate::riscv::sfence;
+use crate::riscv::{flush_tlb_asid, flush_tlb_page};
 use crate::{
-    address::{KernelVAddress, VirtAddr},
+    address::{KernelVAddress, PhysAddr, VirtAddr},
     capability::{Capability, CapabilityData, CapabilityType},
     common::KernelResult,
 };
 
+use super::asid_pool::AsidPoolCap;
 use super::Something;
+use shared::page_size::PageSize;
+
+const PAGE_VPN_MASK: usize = (1 << 45) - 1;
+
+// unix style
+pub const PAGE_RIGHT_X: u8 = 1 << 0;
+pub const PAGE_RIGHT_W: u8 = 1 << 1;
+pub const PAGE_RIGHT_R: u8 = 1 << 2;
+// permission to know physical address of the frame.
+pub const PAGE_RIGHT_DMA: u8 = 1 << 3;
+pub const PAGE_RIGHT_RWX: u8 = PAGE_RIGHT_X | PAGE_RIGHT_W | PAGE_RIGHT_R;
+pub const PAGE_RIGHT_ALL: u8 = PAGE_RIGHT_RWX | PAGE_RIGHT_DMA;
 
 /*
  * PageTable
  * RawCapability[0]
- * | padding 14 | is_root 1 | is_mapped 1 | mapped_address 48 |
- * 64                                            0
+ * | asid 14 | is_root 1 | is_mapped 1 | padding 3 | mapped_vpn 45 |
+ * 64                                                                0
  */
 
 impl KObject for PageTable {}
@@ -29,10 +43,18 @@ impl PageTableCap {
         (!self.is_mapped())
             .then_some(())
             .ok_or(kerr!(ErrKind::PageTableAlreadyMapped))?;
+        root_table
+            .is_root()
+            .then_some(())
+            .ok_or(kerr!(ErrKind::NotRootPageTable))?;
+        let asid = root_table.get_asid();
         let parent_table = root_table.get_pagetable();
         let table = self.get_pagetable();
         let level = table.map(parent_table, vaddr)?;
         self.set_mapped(vaddr);
+        self.set_asid(asid);
+        // non-leaf entry was changed.
+        flush_tlb_asid(asid);
         Ok(level)
     }
 
@@ -47,30 +69,42 @@ impl PageTableCap {
         self.is_root()
             .then_some(())
             .ok_or(kerr!(ErrKind::NotRootPageTable))?;
+        let asid = self.get_asid();
         let page_table = self.get_pagetable();
         unsafe {
-            page_table.activate();
+            page_table.activate(asid);
         }
         Ok(())
     }
 
     fn set_mapped(&mut self, vaddr: VirtAddr) {
-        self.cap_dep_val |=
-            (0x1 << 48) | (<VirtAddr as Into<usize>>::into(vaddr) & 0xffffffffffff) as u64
+        // vpn is stored, because sv57 address doesn't fit in 48 bits.
+        let vpn = <VirtAddr as Into<usize>>::into(vaddr) >> 12;
+        self.cap_dep_val |= (0x1 << 48) | (vpn & PAGE_VPN_MASK) as u64
     }
 
-    pub fn make_as_root(&mut self) -> KernelResult<()> {
+    pub fn make_as_root(&mut self, asid_pool: &mut AsidPoolCap) -> KernelResult<()> {
         (!self.is_mapped())
             .then_some(())
             .ok_or(kerr!(ErrKind::PageTableAlreadyMapped))?;
         let root_table = self.get_pagetable();
+        let asid = asid_pool.assign(root_table)?;
         root_table.copy_global_mapping();
         let addr = VirtAddr::from(root_table as *const PageTable);
         self.set_mapped(addr);
         self.set_root();
+        self.set_asid(asid);
         Ok(())
     }
 
+    pub fn get_asid(&self) -> usize {
+        (self.cap_dep_val >> 50) as usize
+    }
+
+    fn set_asid(&mut self, asid: usize) {
+        self.cap_dep_val |= ((asid & ((1 << ASID_BITS) - 1)) as u64) << 50
+    }
+
     fn is_mapped(&self) -> bool {
         ((self.cap_dep_val >> 48) & 0x1) == 1
     }
@@ -91,9 +125,11 @@ impl Default for PageTable {
 }
 /*
  * Page
+ * RawCapability[1]
+ * cap_right: | padding 4 | dma 1 | read 1 | write 1 | exec 1 |
  * RawCapability[0]
- * | padding 11 | right 3 | is_device 1 | is_mapped 1 | mapped_address 48 |
- * 64                                                                    0
+ * | asid 14 | padding 2 | size 2 | is_mapped 1 | mapped_vpn 45 |
+ * 64                                                          0
  */
 
 impl KObject for Page {}
@@ -110,28 +146,77 @@ impl PageCap {
         (!self.is_mapped())
             .then_some(())
             .ok_or(kerr!(ErrKind::PageAlreadyMapped))?;
+        root_table
+            .is_root()
+            .then_some(())
+            .ok_or(kerr!(ErrKind::NotRootPageTable))?;
+        let asid = root_table.get_asid();
+        let page_size = self.get_page_size();
         let parent_table = root_table.get_pagetable();
         let page = self.get_page();
-        page.map(parent_table, vaddr, flags)?;
+        page.map(parent_table, vaddr, flags, page_size)?;
         self.set_mapped(vaddr);
-        sfence();
-        // TODO:
-        // sfence_vma_vaddr()
+        self.set_asid(asid);
+        flush_tlb_page(vaddr.into(), page_size.bytes(), asid);
         Ok(())
     }
 
-    pub fn unmap(&mut self, root_table: &mut PageTableCap) -> KernelResult<()> {
+    pub fn unmap(&mut self) -> KernelResult<()> {
         self.is_mapped()
             .then_some(())
             .ok_or(kerr!(ErrKind::PageNotMappedYet))?;
         let vaddr = self.get_mapped_address();
+        let asid = self.get_asid();
+        let root_table = find_vspace_for_asid(asid)?;
+        let page_size = self.get_page_size();
         let page = self.get_page();
-        let root_table = root_table.get_pagetable();
-        page.unmap(root_table, vaddr)?;
+        page.unmap(root_table, vaddr, page_size)?;
         self.set_unmapped();
+        flush_tlb_page(vaddr.into(), page_size.bytes(), asid);
+        Ok(())
+    }
+
+    /// Change flags of current mapping in place.
+    pub fn remap(&mut self, flags: usize) -> KernelResult<()> {
+        self.is_mapped()
+            .then_some(())
+            .ok_or(kerr!(ErrKind::PageNotMappedYet))?;
+        let vaddr = self.get_mapped_address();
+        let asid = self.get_asid();
+        let root_table = find_vspace_for_asid(asid)?;
+        let page_size = self.get_page_size();
+        let page = self.get_page();
+        page.remap(root_table, vaddr, flags, page_size)?;
+        flush_tlb_page(vaddr.into(), page_size.bytes(), asid);
         Ok(())
     }
 
+    /// Rights of minted cap are subset of original one.
+    pub fn mint(&mut self, rights: usize) {
+        self.cap_right &= rights as u8 & PAGE_RIGHT_ALL
+    }
+
+    /// Drop read/write/exec bits of user flags which this cap doesn't have.
+    pub fn limit_flags(&self, flags: usize) -> usize {
+        flags & (self.get_rights() | !(PAGE_RIGHT_RWX as usize))
+    }
+
+    /// Physical address of the frame, which is only for cap with dma right.
+    pub fn get_physical_address(&self) -> KernelResult<PhysAddr> {
+        (self.cap_right & PAGE_RIGHT_DMA == PAGE_RIGHT_DMA)
+            .then_some(())
+            .ok_or(kerr!(ErrKind::InvalidOperation))?;
+        Ok(self.get_address())
+    }
+
+    pub fn get_rights(&self) -> usize {
+        self.cap_right as usize
+    }
+
+    pub fn set_rights(&mut self, rights: u8) {
+        self.cap_right = rights & PAGE_RIGHT_ALL
+    }
+
     pub fn get_page(&mut self) -> &mut Page {
         let address = self.get_address();
         let ptr: *mut Page = KernelVAddress::from(address).into();
@@ -139,17 +224,29 @@ impl PageCap {
     }
 
     fn set_mapped(&mut self, vaddr: VirtAddr) {
-        self.cap_dep_val |=
-            (0x1 << 48) | (<VirtAddr as Into<usize>>::into(vaddr) & 0xffffffffffff) as u64
+        let vpn = <VirtAddr as Into<usize>>::into(vaddr) >> 12;
+        self.cap_dep_val |= (0x1 << 45) | (vpn & PAGE_VPN_MASK) as u64
     }
 
     fn set_unmapped(&mut self) {
-        // clear flag, right and mapped address
-        self.cap_dep_val = 0
+        // clear flag, asid and mapped address, but keep size.
+        self.cap_dep_val &= 0x3 << 46
+    }
+
+    pub fn get_asid(&self) -> usize {
+        (self.cap_dep_val >> 50) as usize
+    }
+
+    fn set_asid(&mut self, asid: usize) {
+        self.cap_dep_val |= ((asid & ((1 << ASID_BITS) - 1)) as u64) << 50
     }
 
     fn is_mapped(&self) -> bool {
-        ((self.cap_dep_val >> 48) & 0x1) == 1
+        ((self.cap_dep_val >> 45) & 0x1) == 1
+    }
+
+    pub fn get_page_size(&self) -> PageSize {
+        PageSize::from_level(((self.cap_dep_val >> 46) & 0x3) as usize).unwrap()
     }
 
     pub fn get_address_virtual(&self) -> KernelVAddress {
@@ -157,7 +254,7 @@ impl PageCap {
     }
 
     pub fn get_mapped_address(&self) -> VirtAddr {
-        ((self.cap_dep_val & !(0xffff << 48)) as usize).into()
+        (((self.cap_dep_val as usize) & PAGE_VPN_MASK) << 12).into()
     }
 }
 
@@ -193,10 +290,28 @@ impl Capability for PageCap {
         unsafe {
             *ptr = Page::new();
         }
+        // retyping thread may use the frame as dma buffer, and drops dma right by mint
+        // before giving it to others.
+        self.cap_right = PAGE_RIGHT_ALL;
     }
 
-    fn get_object_size<'a>(_user_size: usize) -> usize {
-        PAGE_SIZE // page size, bytes
+    fn create_cap_dep_val(_addr: KernelVAddress, user_size: usize) -> usize {
+        let page_size = PageSize::try_from(user_size).unwrap_or_default();
+        page_size.level() << 46
+    }
+
+    fn get_object_size<'a>(user_size: usize) -> usize {
+        user_size // page size, bytes
+    }
+
+    fn get_object_align(user_size: usize) -> usize {
+        user_size
+    }
+
+    fn check_user_size(user_size: usize) -> KernelResult<()> {
+        PageSize::try_from(user_size)
+            .map(|_| ())
+            .map_err(|e| kerr!(e))
     }
 
     fn derive(&self, _src_slot: &crate::object::CNodeEntry<Something>) -> KernelResult<Self> {
@@ -205,7 +320,7 @@ impl Capability for PageCap {
             .ok_or(kerr!(ErrKind::PageTableNotMappedYet))?;
 
         let mut cap = self.replicate();
-        cap.cap_dep_val = 0;
+        cap.set_unmapped();
         Ok(cap)
     }
 }
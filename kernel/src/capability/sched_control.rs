@@ -0,0 +1,33 @@
+use super::{Capability, CapabilityData, CapabilityType, Something};
+use crate::common::KernelResult;
+use crate::object::{CNodeEntry, KObject, SchedControl};
+use crate::scheduler::get_idle_time;
+use shared::types::IdleTime;
+
+/*
+ * SchedControl
+ * RawCapability[0]
+ * |                            padding                             |
+ * 64                                                               0
+ */
+
+impl KObject for SchedControl {}
+
+pub type SchedControlCap = CapabilityData<SchedControl>;
+
+impl Capability for SchedControlCap {
+    const CAP_TYPE: CapabilityType = CapabilityType::SchedControl;
+    type KernelObject = SchedControl;
+
+    fn derive(&self, _src_slot: &CNodeEntry<Something>) -> KernelResult<Self> {
+        Ok(self.replicate())
+    }
+
+    fn init_object(&mut self) {}
+}
+
+impl SchedControlCap {
+    pub fn get_idle_time(&self, hartid: usize) -> KernelResult<IdleTime> {
+        get_idle_time(hartid)
+    }
+}
@@ -1,9 +1,12 @@
 use crate::address::KernelVAddress;
 use crate::capability::{Capability, CapabilityType};
-use crate::common::KernelResult;
+use crate::common::{ErrKind, KernelResult};
+use crate::fpu::{release_fpu, sync_fpu_state, SSTATUS_FPU_MASK};
+use crate::kerr;
 use crate::object::page_table::Page;
 use crate::object::{
-    resume, CNode, CNodeEntry, PageTable, Register, ThreadControlBlock, ThreadInfo,
+    resume, suspend, CNode, CNodeEntry, Notification, PageTable, Register, ThreadControlBlock,
+    ThreadInfo,
 };
 use crate::Registers;
 use core::mem;
@@ -20,7 +23,57 @@ impl TCBCap {
 
     pub fn set_registers(&mut self, registers: &Registers) {
         let tcb = self.get_tcb();
+        // state of fpu is managed by kernel.
+        let fpu_state = tcb.registers.sstatus & SSTATUS_FPU_MASK;
         tcb.registers = *registers;
+        tcb.registers.sstatus = (registers.sstatus & !SSTATUS_FPU_MASK) | fpu_state;
+    }
+
+    /// Registers of running thread are being changed, so only not runnable thread can be read.
+    pub fn get_registers(&mut self) -> KernelResult<Registers> {
+        let tcb = self.get_tcb();
+        (!tcb.is_runnable())
+            .then_some(tcb.registers)
+            .ok_or(kerr!(ErrKind::InvalidOperation))
+    }
+
+    /// Copy registers of src to this thread, state of fpu is also copied if copy_fp is true.
+    pub fn copy_registers(&mut self, src: &mut TCBCap, copy_fp: bool) {
+        let src_tcb = src.get_tcb();
+        let registers = src_tcb.registers;
+        self.set_registers(&registers);
+        if copy_fp {
+            sync_fpu_state(src_tcb);
+            let dest_tcb = self.get_tcb();
+            release_fpu(dest_tcb);
+            dest_tcb.fp_registers = src_tcb.fp_registers;
+            #[cfg(feature = "vector")]
+            {
+                dest_tcb.vector_registers = src_tcb.vector_registers.clone();
+            }
+        }
+    }
+
+    /// tp is used as pointer to thread local storage.
+    pub fn set_tls_base(&mut self, tls_base: usize) {
+        self.get_tcb().registers.tp = tls_base;
+    }
+
+    pub fn get_id(&mut self) -> usize {
+        self.get_tcb().tid
+    }
+
+    pub fn suspend(&mut self) -> KernelResult<()> {
+        suspend(self.get_tcb())
+    }
+
+    pub fn bind_death_notification(
+        &mut self,
+        src: &mut CNodeEntry<Notification>,
+    ) -> KernelResult<()> {
+        // badge is kept, so that waiter can know which thread stopped.
+        let notify_cap = src.cap_ref().replicate();
+        self.get_tcb().set_death_notification(notify_cap, src)
     }
 
     pub fn get_tcb(&mut self) -> &mut ThreadControlBlock {
@@ -1,10 +1,11 @@
 use core::marker::PhantomData;
-use core::mem;
+use core::ptr;
 
-use crate::address::KernelVAddress;
+use crate::address::{KernelVAddress, PAGE_SIZE};
 use crate::capability::PhysAddr;
 use crate::capability::{Capability, CapabilityData, CapabilityType};
 use crate::common::{align_up, ErrKind, KernelResult};
+use crate::handler::preemption_point;
 use crate::object::page_table::Page;
 use crate::object::CNode;
 use crate::object::Endpoint;
@@ -16,6 +17,7 @@ use crate::object::ThreadControlBlock;
 use crate::object::Untyped;
 
 use crate::kerr;
+use shared::types::RetypeResult;
 
 /*
  * RawCapability[0]
@@ -23,6 +25,14 @@ use crate::kerr;
  * 64                                                                   0
  */
 
+/// Retyped memory is zero cleared by this size, and timer tick is accounted between chunks.
+const ZERO_CHUNK_SIZE: usize = PAGE_SIZE;
+
+/// Retype from RAM zero clears at most this size per call, because it can't be preempted.
+/// Fewer objects than requested are created if they don't fit, and larger objects
+/// (e.g. 1GiB page) can be retyped only from device memory.
+const MAX_ZERO_SIZE: usize = 0x200000;
+
 impl KObject for Untyped {}
 
 pub type UntypedCap = CapabilityData<Untyped>;
@@ -80,18 +90,37 @@ impl UntypedCap {
                 .then_some(())
                 .ok_or(kerr!(ErrKind::CanNotNewFromDeviceMemory))?
         }
+        <CapabilityData<K>>::check_user_size(user_size)?;
         let block_size = self.block_size();
-        let object_size = num * <CapabilityData<K>>::get_object_size(user_size);
-        let align = mem::align_of::<<CapabilityData<K> as Capability>::KernelObject>();
+        let obj_size = <CapabilityData<K>>::get_object_size(user_size);
+        let num = if is_device {
+            num
+        } else {
+            let max_num = MAX_ZERO_SIZE / obj_size;
+            (max_num != 0)
+                .then_some(())
+                .ok_or(kerr!(ErrKind::InvalidUserSize))?;
+            num.min(max_num)
+        };
+        let object_size = obj_size.checked_mul(num).ok_or(kerr!(ErrKind::NoMemory))?;
+        let align = <CapabilityData<K>>::get_object_align(user_size);
 
         // 2, whether memory is enough or not
+        let free_idx: usize = self.get_free_index().into();
+        let padding = align_up(free_idx, align) - free_idx;
         let free_bytes = self.get_free_bytes();
-        free_bytes
-            .checked_sub(object_size)
+        object_size
+            .checked_add(padding)
+            .and_then(|size| free_bytes.checked_sub(size))
             .ok_or(kerr!(ErrKind::NoMemory))?;
         // 3, create given type capabilities
-        let free_idx_aligned = align_up(self.get_free_index().into(), align).into();
-        let cap_generator = CapGenerator::<K>::new(num, free_idx_aligned, user_size, object_size);
+        let free_idx_aligned = align_up(free_idx, align).into();
+        let cap_generator = CapGenerator::<K>::new(
+            num,
+            free_idx_aligned,
+            user_size,
+            <CapabilityData<K>>::get_object_size(user_size),
+        );
         let new_free_address = cap_generator.end_address;
         // 4, update self information
         let v = Self::create_cap_dep_val(new_free_address, block_size);
@@ -110,7 +139,7 @@ impl UntypedCap {
         user_size: usize,
         num: usize,
         new_type: CapabilityType,
-    ) -> KernelResult<()> {
+    ) -> KernelResult<RetypeResult> {
         match new_type {
             CapabilityType::Tcb => {
                 self.retype_and_insert::<ThreadControlBlock>(mdb, dest_cnode, user_size, num)
@@ -138,16 +167,26 @@ impl UntypedCap {
         dest_cnode: &mut CNode,
         user_size: usize,
         num: usize,
-    ) -> KernelResult<()>
+    ) -> KernelResult<RetypeResult>
     where
         CapabilityData<K>: Capability,
     {
+        let is_device = self.is_device();
         let cap_gen = self.retype::<K>(user_size, num)?;
+        // num may be limited by MAX_ZERO_SIZE.
+        let num = cap_gen.num;
+        // device memory may be mmio, so it must not be written.
+        if !is_device {
+            zero_memory(cap_gen.address, cap_gen.end_address);
+        }
         for (i, mut cap) in cap_gen.enumerate() {
             cap.init_object();
             dest_cnode.insert_cap(src_slot, cap, i);
         }
-        Ok(())
+        Ok(RetypeResult {
+            num,
+            free_offset: self.get_free_offset(),
+        })
     }
 
     pub fn get_free_index(&self) -> KernelVAddress {
@@ -167,6 +206,12 @@ impl UntypedCap {
         self.cap_dep_val &= 0x3f
     }
 
+    /// Offset of free memory from start of this untyped, which is reported to user.
+    pub fn get_free_offset(&self) -> usize {
+        let start_address = KernelVAddress::from(self.get_address());
+        (self.get_free_index() - start_address).into()
+    }
+
     fn get_free_bytes(&self) -> usize {
         let start_address = KernelVAddress::from(self.get_address());
         let end_address = start_address.add(self.block_size());
@@ -174,6 +219,18 @@ impl UntypedCap {
     }
 }
 
+/// Untyped memory is not cleared at boot, so retyped objects must not see stale data.
+fn zero_memory(start: KernelVAddress, end: KernelVAddress) {
+    let mut address: usize = start.into();
+    let end: usize = end.into();
+    while address < end {
+        let len = (end - address).min(ZERO_CHUNK_SIZE);
+        unsafe { ptr::write_bytes(address as *mut u8, 0, len) };
+        address += len;
+        preemption_point();
+    }
+}
+
 pub struct CapGenerator<K>
 where
     K: KObject,
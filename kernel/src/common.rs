@@ -1,9 +1,11 @@
 use core::{error::Error, fmt};
 pub use shared::align_down;
 pub use shared::align_up;
+pub use shared::cap_type::CapabilityType;
 pub use shared::err_kind::ErrKind;
 pub use shared::is_aligned;
 pub use shared::types::BootInfo;
+pub use shared::types::ErrRecord;
 pub use shared::types::IPCBuffer;
 pub use shared::types::UntypedInfo;
 
@@ -14,16 +16,37 @@ pub type KernelResult<T> = Result<T, KernelError>;
 pub struct KernelError {
     pub e_kind: ErrKind,
     pub e_val: u16,
+    pub e_detail: EDetail,
     #[cfg(debug_assertions)]
     pub e_place: EPlace,
 }
 
+/// What was wrong, it is returned to user through ErrRecord.
+#[derive(Debug, Clone, Copy)]
+pub struct EDetail {
+    /// Index of argument register, 0 is a0.
+    pub invalid_arg: Option<usize>,
+    /// (bits resolved, bits left) when capability lookup failed.
+    pub lookup: Option<(u32, u32)>,
+    /// (expected, actual)
+    pub cap_type: Option<(CapabilityType, CapabilityType)>,
+}
+
+impl EDetail {
+    pub const NONE: Self = Self {
+        invalid_arg: None,
+        lookup: None,
+        cap_type: None,
+    };
+}
+
 #[macro_export]
 macro_rules! kerr {
     ($ekind:expr) => {
         $crate::common::KernelError {
             e_kind: $ekind,
             e_val: 0,
+            e_detail: $crate::common::EDetail::NONE,
             #[cfg(debug_assertions)]
             e_place: $crate::common::EPlace {
                 e_line: line!(),
@@ -37,6 +60,7 @@ macro_rules! kerr {
         $crate::common::KernelError {
             e_kind: $ekind,
             e_val: $eval,
+            e_detail: $crate::common::EDetail::NONE,
             #[cfg(debug_assertions)]
             e_place: $crate::common::EPlace {
                 e_line: line!(),
@@ -47,6 +71,53 @@ macro_rules! kerr {
     };
 }
 
+impl KernelError {
+    /// Outermost caller knows which argument was given, so inner one is kept.
+    pub fn with_arg(mut self, reg_idx: usize) -> Self {
+        self.e_detail.invalid_arg.get_or_insert(reg_idx);
+        self
+    }
+
+    pub fn with_lookup(mut self, resolved_bits: u32, left_bits: u32) -> Self {
+        self.e_detail.lookup = Some((resolved_bits, left_bits));
+        self
+    }
+
+    pub fn with_cap_type(mut self, expected: CapabilityType, actual: CapabilityType) -> Self {
+        self.e_detail.cap_type = Some((expected, actual));
+        self
+    }
+
+    pub fn to_record(&self) -> ErrRecord {
+        let mut record = ErrRecord::new(self.e_kind, self.e_val as usize);
+        if let Some(reg_idx) = self.e_detail.invalid_arg {
+            record.invalid_arg = reg_idx;
+        }
+        if let Some((resolved_bits, left_bits)) = self.e_detail.lookup {
+            record.failed_depth = resolved_bits as usize;
+            record.bits_left = left_bits as usize;
+        }
+        if let Some((expected, actual)) = self.e_detail.cap_type {
+            record.expected_cap_type = expected as usize;
+            record.actual_cap_type = actual as usize;
+        }
+        #[cfg(debug_assertions)]
+        record.set_place(self.e_place.e_file, self.e_place.e_line);
+        record
+    }
+}
+
+/// Tag error with the argument register which caused it.
+pub trait ArgContext {
+    fn for_arg(self, reg_idx: usize) -> Self;
+}
+
+impl<T> ArgContext for KernelResult<T> {
+    fn for_arg(self, reg_idx: usize) -> Self {
+        self.map_err(|e| e.with_arg(reg_idx))
+    }
+}
+
 impl fmt::Display for KernelError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -0,0 +1,147 @@
+use crate::address::{KernelVAddress, PhysAddr, VirtAddr, PAGE_SIZE};
+use crate::common::{ErrKind, KernelResult};
+use crate::kerr;
+use crate::object::{Registers, ThreadControlBlock};
+use crate::println;
+use crate::sbi::sbi_system_shutdown;
+use crate::scheduler::{dump_scheduler, get_current_tcb_mut};
+use crate::syscall::InvLabel;
+use crate::uart::{flush_console, putchar};
+
+use core::{cmp::min, fmt, str};
+
+/// DebugPutString can't print more than this at once, so that one trap doesn't take too long.
+const PUT_STRING_MAX: usize = PAGE_SIZE;
+pub const THREAD_NAME_LEN: usize = 16;
+
+/// Name of thread given by DebugNameThread, longer name is truncated.
+#[derive(Default)]
+pub struct ThreadName {
+    bytes: [u8; THREAD_NAME_LEN],
+    len: usize,
+}
+
+impl ThreadName {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; THREAD_NAME_LEN],
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Debug for ThreadName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = str::from_utf8(&self.bytes[..self.len]).unwrap_or("<invalid utf-8>");
+        write!(f, "{:?}", name)
+    }
+}
+
+/// Handle SysCallNo::Debug, arguments are given by a0, a1, a3 and a4.
+pub fn handle_debug_syscall(inv_label: InvLabel, reg: &Registers) -> KernelResult<Option<usize>> {
+    match inv_label {
+        InvLabel::DebugPutString => {
+            put_string(reg.a0, reg.a1)?;
+            Ok(None)
+        }
+        InvLabel::DebugHalt => {
+            println!("kernel halted by {:?}", get_current_tcb_mut().name);
+            flush_console();
+            sbi_system_shutdown();
+            #[allow(clippy::empty_loop)]
+            loop {}
+        }
+        InvLabel::DebugDumpScheduler => {
+            dump_scheduler();
+            Ok(None)
+        }
+        InvLabel::DebugNameThread => {
+            let tcb = lookup_tcb(reg.a0, reg.a1 as u32)?;
+            let mut name = ThreadName::new();
+            name.len = min(reg.a4, THREAD_NAME_LEN);
+            let mut offset = 0;
+            for_each_user_chunk(reg.a3, name.len, |chunk| {
+                name.bytes[offset..offset + chunk.len()].copy_from_slice(chunk);
+                offset += chunk.len();
+            })?;
+            tcb.name = name;
+            Ok(None)
+        }
+        InvLabel::DebugCapIdentify => {
+            // 0 means empty slot, because cap type starts from 1.
+            let mut root_cnode = get_current_tcb_mut()
+                .root_cnode
+                .as_ref()
+                .unwrap()
+                .cap_ref()
+                .replicate();
+            let slot = root_cnode.lookup_entry_mut(reg.a0, reg.a1 as u32)?;
+            match slot {
+                Some(entry) => Ok(Some(entry.get_cap_type()? as usize)),
+                None => Ok(Some(0)),
+            }
+        }
+        _ => Err(kerr!(ErrKind::UnknownInvocation)),
+    }
+}
+
+fn put_string(vaddr: usize, len: usize) -> KernelResult<()> {
+    (len <= PUT_STRING_MAX)
+        .then_some(())
+        .ok_or(kerr!(ErrKind::InvalidOperation))?;
+    for_each_user_chunk(vaddr, len, |chunk| {
+        for ch in chunk {
+            putchar(*ch)
+        }
+    })
+}
+
+fn lookup_tcb<'a>(cap_ptr: usize, cap_depth: u32) -> KernelResult<&'a mut ThreadControlBlock> {
+    let mut root_cnode = get_current_tcb_mut()
+        .root_cnode
+        .as_ref()
+        .unwrap()
+        .cap_ref()
+        .replicate();
+    let tcb_cap = root_cnode
+        .lookup_entry_mut(cap_ptr, cap_depth)?
+        .as_mut()
+        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
+        .cap_ref_mut()
+        .try_ref_mut_as::<ThreadControlBlock>()?;
+    let tcb: *mut ThreadControlBlock = tcb_cap.get_tcb();
+    Ok(unsafe { &mut *tcb })
+}
+
+/// Call f with user memory [vaddr, vaddr + len) of current thread, split at page boundaries.
+/// Memory is read through physmap after walking page table of the thread,
+/// so that bad address results in error instead of page fault in kernel.
+fn for_each_user_chunk<F>(vaddr: usize, len: usize, mut f: F) -> KernelResult<()>
+where
+    F: FnMut(&[u8]),
+{
+    let vspace = get_current_tcb_mut()
+        .vspace
+        .as_mut()
+        .ok_or(kerr!(ErrKind::PageTableNotMappedYet))?
+        .cap_ref_mut()
+        .get_pagetable();
+    let end = vaddr
+        .checked_add(len)
+        .ok_or(kerr!(ErrKind::InvalidUserAddress))?;
+    let mut cur = vaddr;
+    while cur < end {
+        let (level, pte) = vspace.walk(VirtAddr::from(cur), 0);
+        if !pte.is_user_readable() {
+            return Err(kerr!(ErrKind::InvalidUserAddress));
+        }
+        let page_size = 1 << (12 + 9 * level);
+        let offset = cur & (page_size - 1);
+        let chunk_len = min(end - cur, page_size - offset);
+        let paddr: PhysAddr = pte.get_address().add(offset);
+        let ptr: *const u8 = KernelVAddress::from(paddr).into();
+        f(unsafe { core::slice::from_raw_parts(ptr, chunk_len) });
+        cur += chunk_len;
+    }
+    Ok(())
+}
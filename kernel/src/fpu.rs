@@ -0,0 +1,299 @@
+use core::arch::asm;
+use core::mem::offset_of;
+use core::ptr;
+
+use crate::object::ThreadControlBlock;
+use crate::riscv::{
+    r_sstatus, w_sstatus, SSTATUS_FS, SSTATUS_FS_CLEAN, SSTATUS_FS_DIRTY, SSTATUS_FS_OFF,
+};
+#[cfg(feature = "vector")]
+use crate::riscv::{SSTATUS_VS, SSTATUS_VS_CLEAN, SSTATUS_VS_DIRTY};
+use crate::scheduler::{get_current_tcb_mut, CPU_VAR};
+use shared::registers::FpRegisters;
+
+/*
+ Lazy context switch of floating-point (and vector) state.
+ Registers of fpu hold state of the owner of this hart, and only the owner runs with
+ sstatus.FS (and VS) other than Off. Other threads trap with illegal instruction when they
+ use fpu, then the owner's state is saved only if it is Dirty, and the state of trapped thread is loaded.
+ Initial is treated as Clean, because loaded state of new thread is all zero.
+*/
+
+/// Kernel managed bits of sstatus, which user cannot change by writing registers.
+#[cfg(not(feature = "vector"))]
+pub const SSTATUS_FPU_MASK: usize = SSTATUS_FS;
+#[cfg(feature = "vector")]
+pub const SSTATUS_FPU_MASK: usize = SSTATUS_FS | SSTATUS_VS;
+#[cfg(not(feature = "vector"))]
+const SSTATUS_FPU_CLEAN: usize = SSTATUS_FS_CLEAN;
+#[cfg(feature = "vector")]
+const SSTATUS_FPU_CLEAN: usize = SSTATUS_FS_CLEAN | SSTATUS_VS_CLEAN;
+
+/// Called on illegal instruction.
+/// Returns false if the trap was not caused by disabled fpu.
+pub fn handle_fpu_trap() -> bool {
+    let current = get_current_tcb_mut();
+    if current.registers.sstatus & SSTATUS_FS != SSTATUS_FS_OFF {
+        // current is the owner, so it was a real illegal instruction.
+        return false;
+    }
+    unsafe {
+        let owner = CPU_VAR.fpu_owner;
+        if !ptr::eq(owner, current) {
+            // make registers of fpu accessible from kernel,
+            // sstatus is restored from current's registers on returning to user.
+            w_sstatus(r_sstatus() | SSTATUS_FPU_MASK);
+            if let Some(owner) = owner.as_mut() {
+                save_fpu_state(owner);
+                owner.registers.sstatus &= !SSTATUS_FPU_MASK;
+            }
+            restore_fpu_state(current);
+            CPU_VAR.fpu_owner = current;
+        }
+    }
+    current.registers.sstatus = (current.registers.sstatus & !SSTATUS_FPU_MASK) | SSTATUS_FPU_CLEAN;
+    true
+}
+
+/// Write state in registers of fpu back to the thread, if it is the owner.
+pub fn sync_fpu_state(thread: &mut ThreadControlBlock) {
+    unsafe {
+        if ptr::eq(CPU_VAR.fpu_owner, thread) {
+            w_sstatus(r_sstatus() | SSTATUS_FPU_MASK);
+            save_fpu_state(thread);
+            thread.registers.sstatus =
+                (thread.registers.sstatus & !SSTATUS_FPU_MASK) | SSTATUS_FPU_CLEAN;
+        }
+    }
+}
+
+/// Drop state in registers of fpu if the thread is the owner,
+/// so that the thread loads its state on next use, e.g. after the state was overwritten.
+pub fn release_fpu(thread: &mut ThreadControlBlock) {
+    unsafe {
+        if ptr::eq(CPU_VAR.fpu_owner, thread) {
+            CPU_VAR.fpu_owner = ptr::null_mut();
+        }
+    }
+    thread.registers.sstatus &= !SSTATUS_FPU_MASK;
+}
+
+unsafe fn save_fpu_state(owner: &mut ThreadControlBlock) {
+    if owner.registers.sstatus & SSTATUS_FS == SSTATUS_FS_DIRTY {
+        save_fp_registers(&mut owner.fp_registers);
+    }
+    #[cfg(feature = "vector")]
+    if owner.registers.sstatus & SSTATUS_VS == SSTATUS_VS_DIRTY {
+        vector::save_vector_registers(&mut owner.vector_registers);
+    }
+}
+
+unsafe fn restore_fpu_state(thread: &mut ThreadControlBlock) {
+    restore_fp_registers(&thread.fp_registers);
+    #[cfg(feature = "vector")]
+    vector::restore_vector_registers(&thread.vector_registers);
+}
+
+unsafe fn save_fp_registers(regs: &mut FpRegisters) {
+    asm!(
+            "fsd f0, 0({regs})",
+            "fsd f1, 8({regs})",
+            "fsd f2, 16({regs})",
+            "fsd f3, 24({regs})",
+            "fsd f4, 32({regs})",
+            "fsd f5, 40({regs})",
+            "fsd f6, 48({regs})",
+            "fsd f7, 56({regs})",
+            "fsd f8, 64({regs})",
+            "fsd f9, 72({regs})",
+            "fsd f10, 80({regs})",
+            "fsd f11, 88({regs})",
+            "fsd f12, 96({regs})",
+            "fsd f13, 104({regs})",
+            "fsd f14, 112({regs})",
+            "fsd f15, 120({regs})",
+            "fsd f16, 128({regs})",
+            "fsd f17, 136({regs})",
+            "fsd f18, 144({regs})",
+            "fsd f19, 152({regs})",
+            "fsd f20, 160({regs})",
+            "fsd f21, 168({regs})",
+            "fsd f22, 176({regs})",
+            "fsd f23, 184({regs})",
+            "fsd f24, 192({regs})",
+            "fsd f25, 200({regs})",
+            "fsd f26, 208({regs})",
+            "fsd f27, 216({regs})",
+            "fsd f28, 224({regs})",
+            "fsd f29, 232({regs})",
+            "fsd f30, 240({regs})",
+            "fsd f31, 248({regs})",
+            "frcsr {tmp}",
+            "sd {tmp}, {fcsr_offset}({regs})",
+            regs = in(reg) regs as *mut FpRegisters,
+            tmp = out(reg) _,
+            fcsr_offset = const offset_of!(FpRegisters, fcsr),
+    )
+}
+
+unsafe fn restore_fp_registers(regs: &FpRegisters) {
+    asm!(
+            "fld f0, 0({regs})",
+            "fld f1, 8({regs})",
+            "fld f2, 16({regs})",
+            "fld f3, 24({regs})",
+            "fld f4, 32({regs})",
+            "fld f5, 40({regs})",
+            "fld f6, 48({regs})",
+            "fld f7, 56({regs})",
+            "fld f8, 64({regs})",
+            "fld f9, 72({regs})",
+            "fld f10, 80({regs})",
+            "fld f11, 88({regs})",
+            "fld f12, 96({regs})",
+            "fld f13, 104({regs})",
+            "fld f14, 112({regs})",
+            "fld f15, 120({regs})",
+            "fld f16, 128({regs})",
+            "fld f17, 136({regs})",
+            "fld f18, 144({regs})",
+            "fld f19, 152({regs})",
+            "fld f20, 160({regs})",
+            "fld f21, 168({regs})",
+            "fld f22, 176({regs})",
+            "fld f23, 184({regs})",
+            "fld f24, 192({regs})",
+            "fld f25, 200({regs})",
+            "fld f26, 208({regs})",
+            "fld f27, 216({regs})",
+            "fld f28, 224({regs})",
+            "fld f29, 232({regs})",
+            "fld f30, 240({regs})",
+            "fld f31, 248({regs})",
+            "ld {tmp}, {fcsr_offset}({regs})",
+            "fscsr {tmp}",
+            regs = in(reg) regs as *const FpRegisters,
+            tmp = out(reg) _,
+            fcsr_offset = const offset_of!(FpRegisters, fcsr),
+    )
+}
+
+#[cfg(feature = "vector")]
+pub mod vector {
+    use core::arch::asm;
+    use core::mem::offset_of;
+
+    /// Largest VLEN in bytes which can be saved, VLEN is 256 bits.
+    pub const MAX_VLENB: usize = 32;
+
+    #[repr(C)]
+    #[derive(Debug, Clone)]
+    pub struct VectorRegisters {
+        pub vstart: usize,
+        pub vl: usize,
+        pub vtype: usize,
+        pub vcsr: usize,
+        pub v: [u8; 32 * MAX_VLENB],
+    }
+
+    impl VectorRegisters {
+        pub const fn null() -> Self {
+            Self {
+                vstart: 0,
+                vl: 0,
+                vtype: 0,
+                vcsr: 0,
+                v: [0; 32 * MAX_VLENB],
+            }
+        }
+    }
+
+    impl Default for VectorRegisters {
+        fn default() -> Self {
+            Self::null()
+        }
+    }
+
+    fn vlenb() -> usize {
+        let vlenb: usize;
+        unsafe {
+            asm!(".option push", ".option arch, +v", "csrr {0}, vlenb", ".option pop", out(reg) vlenb)
+        };
+        assert!(
+            vlenb <= MAX_VLENB,
+            "VLEN {} bits is not supported",
+            vlenb * 8
+        );
+        vlenb
+    }
+
+    pub unsafe fn save_vector_registers(regs: &mut VectorRegisters) {
+        // each vs8r.v stores 8 registers.
+        let step = vlenb() * 8;
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "csrr {tmp}, vstart",
+            "sd {tmp}, {vstart_offset}({regs})",
+            "csrr {tmp}, vl",
+            "sd {tmp}, {vl_offset}({regs})",
+            "csrr {tmp}, vtype",
+            "sd {tmp}, {vtype_offset}({regs})",
+            "csrr {tmp}, vcsr",
+            "sd {tmp}, {vcsr_offset}({regs})",
+            "csrw vstart, x0",
+            "addi {tmp}, {regs}, {v_offset}",
+            "vs8r.v v0, ({tmp})",
+            "add {tmp}, {tmp}, {step}",
+            "vs8r.v v8, ({tmp})",
+            "add {tmp}, {tmp}, {step}",
+            "vs8r.v v16, ({tmp})",
+            "add {tmp}, {tmp}, {step}",
+            "vs8r.v v24, ({tmp})",
+            ".option pop",
+            regs = in(reg) regs as *mut VectorRegisters,
+            step = in(reg) step,
+            tmp = out(reg) _,
+            vstart_offset = const offset_of!(VectorRegisters, vstart),
+            vl_offset = const offset_of!(VectorRegisters, vl),
+            vtype_offset = const offset_of!(VectorRegisters, vtype),
+            vcsr_offset = const offset_of!(VectorRegisters, vcsr),
+            v_offset = const offset_of!(VectorRegisters, v),
+        )
+    }
+
+    pub unsafe fn restore_vector_registers(regs: &VectorRegisters) {
+        let step = vlenb() * 8;
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "csrw vstart, x0",
+            "addi {tmp}, {regs}, {v_offset}",
+            "vl8r.v v0, ({tmp})",
+            "add {tmp}, {tmp}, {step}",
+            "vl8r.v v8, ({tmp})",
+            "add {tmp}, {tmp}, {step}",
+            "vl8r.v v16, ({tmp})",
+            "add {tmp}, {tmp}, {step}",
+            "vl8r.v v24, ({tmp})",
+            // vl and vtype can be restored only by vsetvl.
+            "ld {tmp}, {vl_offset}({regs})",
+            "ld {tmp2}, {vtype_offset}({regs})",
+            "vsetvl x0, {tmp}, {tmp2}",
+            "ld {tmp}, {vstart_offset}({regs})",
+            "csrw vstart, {tmp}",
+            "ld {tmp}, {vcsr_offset}({regs})",
+            "csrw vcsr, {tmp}",
+            ".option pop",
+            regs = in(reg) regs as *const VectorRegisters,
+            step = in(reg) step,
+            tmp = out(reg) _,
+            tmp2 = out(reg) _,
+            vstart_offset = const offset_of!(VectorRegisters, vstart),
+            vl_offset = const offset_of!(VectorRegisters, vl),
+            vtype_offset = const offset_of!(VectorRegisters, vtype),
+            vcsr_offset = const offset_of!(VectorRegisters, vcsr),
+            v_offset = const offset_of!(VectorRegisters, v),
+        )
+    }
+}
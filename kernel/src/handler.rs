@@ -4,13 +4,22 @@ use core::{
 };
 
 use crate::{
+    fpu::handle_fpu_trap,
     object::Registers,
-    riscv::{r_scause, r_sepc, r_stval},
+    pmu::handle_counter_overflow,
+    riscv::{r_scause, r_sepc, r_sip, r_stval, SIP_STIP},
     scheduler::{get_current_reg, get_current_tcb_mut, schedule, timer_tick, CpuVar},
+    stack::find_overflowed_stack,
     syscall::handle_syscall,
     timer::set_timer,
+    uart::drain_console,
 };
 
+#[cfg(feature = "trace")]
+use crate::trace::trace_trap;
+#[cfg(debug_assertions)]
+use crate::{scheduler::get_hartid, stack::check_canary};
+
 // I wanna use enum;
 /// Interrupts,
 const SUPERVISORSOFTWARE: usize = 1;
@@ -147,20 +156,34 @@ pub extern "C" fn trap_entry() {
     }
 }
 
+fn handle_timer() {
+    timer_tick();
+    set_timer(10000);
+    drain_console();
+}
+
+/// Called between chunks of long running work in kernel, because interrupts are disabled there.
+/// Only timer tick is accounted here, thread is switched when returning to user, so the work
+/// itself must be bounded.
+pub fn preemption_point() {
+    if r_sip() & SIP_STIP != 0 {
+        handle_timer();
+    }
+}
+
 #[no_mangle]
 fn handle_trap() -> ! {
     let scause = r_scause();
     let code = scause & !(1 << (usize::BITS - 1));
     let stval = r_stval();
     let user_pc = r_sepc();
+    #[cfg(feature = "trace")]
+    trace_trap(scause, user_pc);
 
     if (scause >> (usize::BITS - 1)) == 1 {
         //  interrupt
         match code {
-            SUPREVISORTIMER => {
-                timer_tick();
-                set_timer(10000);
-            }
+            SUPREVISORTIMER => handle_timer(),
             SUPERVISORSOFTWARE => {
                 panic!(
                     "supervisor software scause={:x}, stval={:x}, sepc={:x}",
@@ -173,12 +196,7 @@ fn handle_trap() -> ! {
                     code, stval, user_pc
                 )
             }
-            COUNTER_OVERFLOW => {
-                panic!(
-                    "counter overflow scause={:x}, stval={:x}, sepc={:x}",
-                    code, stval, user_pc
-                )
-            }
+            COUNTER_OVERFLOW => handle_counter_overflow(),
             _ => {
                 panic!(
                     "unexpected interrupt scause={:x}, stval={:x}, sepc={:x}",
@@ -206,10 +224,13 @@ fn handle_trap() -> ! {
                 );
             }
             ILEAGALI => {
-                panic!(
-                    "inst ileagal scause={:x}, stval={:x}, sepc={:x}",
-                    scause, stval, user_pc
-                );
+                // first use of fpu by the thread which is not owner, retry the instruction.
+                if !handle_fpu_trap() {
+                    panic!(
+                        "inst ileagal scause={:x}, stval={:x}, sepc={:x}",
+                        scause, stval, user_pc
+                    );
+                }
             }
             BREAKPOINT => {
                 panic!(
@@ -254,12 +275,24 @@ fn handle_trap() -> ! {
                 );
             }
             LPAGEFAULT => {
+                if let Some(stack) = find_overflowed_stack(stval) {
+                    panic!(
+                        "kernel stack overflow on {}, stval={:x}, sepc={:x}",
+                        stack, stval, user_pc
+                    );
+                }
                 panic!(
                     "load page fault scause={:x}, stval={:x}, sepc={:x}",
                     scause, stval, user_pc
                 );
             }
             SAPAGEFAULT => {
+                if let Some(stack) = find_overflowed_stack(stval) {
+                    panic!(
+                        "kernel stack overflow on {}, stval={:x}, sepc={:x}",
+                        stack, stval, user_pc
+                    );
+                }
                 panic!(
                     "store/amo page fault, scause={:x}, stval={:x}, sepc={:x}",
                     scause, stval, user_pc
@@ -273,6 +306,8 @@ fn handle_trap() -> ! {
             }
         }
     }
+    #[cfg(debug_assertions)]
+    check_canary(get_hartid());
     unsafe {
         schedule();
         return_to_user()
@@ -1,39 +1,63 @@
 use self::root_server::{
-    ROOT_BOOT_INFO_PAGE, ROOT_CNODE_ENTRY_NUM_BITS, ROOT_CNODE_IDX, ROOT_VSPACE_IDX,
+    ROOT_ASID_CONTROL_IDX, ROOT_ASID_POOL_IDX, ROOT_BENCHMARK_IDX, ROOT_CNODE_ENTRY_NUM_BITS,
+    ROOT_CNODE_IDX, ROOT_SCHED_CONTROL_IDX, ROOT_VSPACE_IDX,
 };
 use crate::address::PAGE_SIZE;
 use crate::common::{BootInfo, UntypedInfo};
-use crate::scheduler::{create_idle_thread, require_schedule, schedule};
+use crate::scheduler::{create_idle_thread, get_hartid, require_schedule, schedule};
 use shared::elf::def::Elf64Hdr;
 use shared::registers::Register;
+#[cfg(feature = "trace")]
+use shared::trace::TRACE_BUFFER_PAGES;
 
 mod pm;
 mod root_server;
 mod vm;
 
 use crate::handler::trap_entry;
-use crate::println;
-use crate::riscv::{r_sie, w_sie, w_sscratch, w_stvec, SIE_SEIE, SIE_SSIE, SIE_STIE};
+use crate::log_info;
+use crate::object::asid_pool::set_asid_len;
+use crate::object::PageTable;
+use crate::pmu::init_pmu;
+use crate::riscv::{
+    mark_hart_online, r_sie, w_sie, w_sscratch, w_stvec, SIE_SEIE, SIE_SSIE, SIE_STIE,
+};
 use crate::scheduler::CPU_VAR;
+#[cfg(debug_assertions)]
+use crate::stack::init_canary;
+use crate::stack::stack_top;
 use crate::timer::{set_timer, MTIME_PER_1MS};
 use pm::BumpAllocator;
 use root_server::{RootServerMemory, RootServerResourceManager};
 use vm::kernel_vm_init;
 
-extern "C" {
-    static __stack_top: u8;
-}
-
-pub fn init_kernel(elf_header: *const Elf64Hdr, free_ram_phys: usize, free_ram_end_phys: usize) {
-    println!("initialising kernel");
+pub fn init_kernel(
+    hartid: usize,
+    elf_header: *const Elf64Hdr,
+    free_ram_phys: usize,
+    free_ram_end_phys: usize,
+) {
+    log_info!("initialising kernel");
+    unsafe {
+        CPU_VAR.hartid = hartid;
+        // traps are handled on its own stack.
+        CPU_VAR.sptop = stack_top(hartid);
+    }
+    #[cfg(debug_assertions)]
+    init_canary(hartid);
+    mark_hart_online(hartid);
     w_stvec(trap_entry as usize);
-    let bump_allocator = unsafe { BumpAllocator::new(free_ram_phys, free_ram_end_phys) };
-    unsafe { kernel_vm_init(free_ram_end_phys) };
+    let mut bump_allocator = unsafe { BumpAllocator::new(free_ram_phys, free_ram_end_phys) };
+    unsafe { kernel_vm_init(&mut bump_allocator, free_ram_end_phys) };
+    let asid_len = unsafe { PageTable::probe_asid_len() };
+    log_info!("asid length is {} bits", asid_len);
+    set_asid_len(asid_len);
     w_sie(r_sie() | SIE_SEIE | SIE_STIE | SIE_SSIE);
+    init_pmu();
     init_root_server(bump_allocator, elf_header);
     w_sscratch(&raw const CPU_VAR as usize);
     set_timer(MTIME_PER_1MS);
-    println!("initialization finished");
+    log_info!("initialization finished");
 }
 
 fn create_initial_thread(
@@ -44,9 +68,16 @@ fn create_initial_thread(
     // 8, call return_to_user(after returning user, to clear stack)
     // 1, create root cnode and insert self cap into self(root cnode)
     let mut root_cnode_cap = root_server_mem.create_root_cnode();
-    // 2, create vm space for root server,
-    let (mut vspace_cap, max_vaddr) =
-        root_server_mem.create_address_space(&mut root_cnode_cap, elf_header, &mut bootstage_mbr);
+    // 2, create asid pool and vm space for root server,
+    let mut asid_pool_cap = root_server_mem.create_asid_pool(&mut root_cnode_cap);
+    root_server_mem.create_sched_control(&mut root_cnode_cap);
+    root_server_mem.create_benchmark(&mut root_cnode_cap);
+    let (mut vspace_cap, max_vaddr) = root_server_mem.create_address_space(
+        &mut root_cnode_cap,
+        &mut asid_pool_cap,
+        elf_header,
+        &mut bootstage_mbr,
+    );
     // 3, create ipc buffer frame
     let mut ipc_page_cap = root_server_mem.create_ipc_buf_frame(
         &mut root_cnode_cap,
@@ -61,8 +92,11 @@ fn create_initial_thread(
         max_vaddr.add(PAGE_SIZE),
         &mut bootstage_mbr,
     );
+    #[cfg(feature = "trace")]
+    let trace_buffer_idx =
+        root_server_mem.create_trace_buffer_frames(&mut root_cnode_cap, &mut bootstage_mbr);
     // 4, create idle thread
-    create_idle_thread(&raw const __stack_top as usize);
+    create_idle_thread(get_hartid());
     // 5, create root server tcb,
     let boot_info_ptr: *mut BootInfo = boot_info_addr.into();
     let boot_info = unsafe {
@@ -97,18 +131,28 @@ fn create_initial_thread(
     }
     boot_info.root_cnode_idx = ROOT_CNODE_IDX;
     boot_info.root_vspace_idx = ROOT_VSPACE_IDX;
+    boot_info.asid_control_idx = ROOT_ASID_CONTROL_IDX;
+    boot_info.asid_pool_idx = ROOT_ASID_POOL_IDX;
+    boot_info.sched_control_idx = ROOT_SCHED_CONTROL_IDX;
+    boot_info.benchmark_idx = ROOT_BENCHMARK_IDX;
+    boot_info.boot_hartid = get_hartid();
+    #[cfg(feature = "trace")]
+    {
+        boot_info.trace_buffer_idx = trace_buffer_idx;
+        boot_info.trace_buffer_pages = TRACE_BUFFER_PAGES;
+    }
     boot_info.ipc_buffer_addr = max_vaddr.add(PAGE_SIZE).into();
     // 7, set initial thread into current thread
     root_tcb.set_register(&[(Register::A0, max_vaddr.add(PAGE_SIZE * 2).into())]);
     root_tcb.make_runnable();
-    println!("root process initialization finished");
+    log_info!("root process initialization finished");
 }
 
 pub fn init_root_server(mut bump_allocator: BumpAllocator, elf_header: *const Elf64Hdr) {
     let mut root_server_mem = RootServerMemory::init_with_uninit(&mut bump_allocator);
     let bootstage_mbr = RootServerResourceManager::new(
         bump_allocator,
-        ROOT_BOOT_INFO_PAGE + 1,
+        ROOT_BENCHMARK_IDX + 1,
         2_usize.pow(ROOT_CNODE_ENTRY_NUM_BITS as u32) - 1,
     );
     create_initial_thread(&mut root_server_mem, bootstage_mbr, elf_header);
@@ -5,20 +5,29 @@ use shared::elf::ProgramMapper;
 use crate::address::KernelVAddress;
 use crate::address::VirtAddr;
 use crate::address::PAGE_SIZE;
+use crate::capability::asid_pool::{AsidControlCap, AsidPoolCap};
+use crate::capability::benchmark::BenchmarkCap;
 use crate::capability::cnode::CNodeCap;
-use crate::capability::page_table::PageCap;
 use crate::capability::page_table::PageTableCap;
+use crate::capability::page_table::{PageCap, PAGE_RIGHT_ALL};
+use crate::capability::sched_control::SchedControlCap;
 use crate::capability::tcb::TCBCap;
 use crate::capability::untyped::UntypedCap;
 use crate::capability::CapInSlot;
 use crate::capability::Capability;
 use crate::common::{align_up, ErrKind};
+use crate::object::asid_pool::register_asid_pool;
 use crate::object::page_table::{Page, PAGE_R, PAGE_U, PAGE_W, PAGE_X};
+use crate::object::AsidPool;
 use crate::object::CNodeEntry;
 use crate::object::PageTable;
 use crate::object::ThreadControlBlock;
 use crate::object::ThreadInfo;
 use crate::object::{CNode, CSlot};
+#[cfg(feature = "trace")]
+use crate::{capability::page_table::PAGE_RIGHT_R, trace::init_trace};
+#[cfg(feature = "trace")]
+use shared::trace::{TraceBuffer, TRACE_BUFFER_PAGES};
 
 use crate::riscv::SSTATUS_SPIE;
 use crate::KernelError;
@@ -31,6 +40,10 @@ pub const ROOT_CNODE_IDX: usize = 2;
 pub const ROOT_VSPACE_IDX: usize = 3;
 pub const ROOT_IPC_BUFFER: usize = 4;
 pub const ROOT_BOOT_INFO_PAGE: usize = 5;
+pub const ROOT_ASID_CONTROL_IDX: usize = 6;
+pub const ROOT_ASID_POOL_IDX: usize = 7;
+pub const ROOT_SCHED_CONTROL_IDX: usize = 8;
+pub const ROOT_BENCHMARK_IDX: usize = 9;
 pub const ROOT_CNODE_ENTRY_NUM_BITS: usize = 18; // 2^18
 
 impl CNode {
@@ -58,6 +71,9 @@ pub(in crate::init) struct RootServerMemory<'a> {
     tcb: &'a mut MaybeUninit<ThreadControlBlock>,
     ipc_buf: &'a mut MaybeUninit<Page>,
     boot_frame: &'a mut MaybeUninit<Page>,
+    asid_pool: &'a mut MaybeUninit<AsidPool>,
+    #[cfg(feature = "trace")]
+    trace_buf: KernelVAddress,
 }
 
 impl<'a> RootServerMemory<'a> {
@@ -80,14 +96,20 @@ impl<'a> RootServerMemory<'a> {
         let cnode = Self::alloc_obj::<CNodeCap>(bump_allocator, ROOT_CNODE_ENTRY_NUM_BITS);
         let vspace = Self::alloc_obj::<PageTableCap>(bump_allocator, 0);
         let tcb = Self::alloc_obj::<TCBCap>(bump_allocator, 0);
-        let ipc_buf = Self::alloc_obj::<PageCap>(bump_allocator, 0);
-        let boot_frame = Self::alloc_obj::<PageCap>(bump_allocator, 0);
+        let ipc_buf = Self::alloc_obj::<PageCap>(bump_allocator, PAGE_SIZE);
+        let boot_frame = Self::alloc_obj::<PageCap>(bump_allocator, PAGE_SIZE);
+        let asid_pool = Self::alloc_obj::<AsidPoolCap>(bump_allocator, 0);
+        #[cfg(feature = "trace")]
+        let trace_buf = bump_allocator.allocate_pages(TRACE_BUFFER_PAGES).into();
         Self {
             cnode,
             vspace,
             tcb,
             ipc_buf,
             boot_frame,
+            asid_pool,
+            #[cfg(feature = "trace")]
+            trace_buf,
         }
     }
 
@@ -101,10 +123,35 @@ impl<'a> RootServerMemory<'a> {
         cap
     }
 
+    /// create asid control and the first asid pool, whose asid base is 0.
+    pub fn create_asid_pool(&mut self, cnode_cap: &mut CNodeCap) -> AsidPoolCap {
+        let asid_pool = self.asid_pool.write(AsidPool::new());
+        register_asid_pool(0, asid_pool).unwrap();
+        let vaddr = (asid_pool as *const AsidPool).into();
+        let cap = AsidPoolCap::init_with_base(vaddr, 0);
+        cnode_cap.write_slot(cap.replicate(), ROOT_ASID_POOL_IDX);
+        let control_cap = AsidControlCap::init(0.into(), 0);
+        cnode_cap.write_slot(control_cap, ROOT_ASID_CONTROL_IDX);
+        cap
+    }
+
+    /// create sched control, there is no object behind it.
+    pub fn create_sched_control(&mut self, cnode_cap: &mut CNodeCap) {
+        let cap = SchedControlCap::init(0.into(), 0);
+        cnode_cap.write_slot(cap, ROOT_SCHED_CONTROL_IDX);
+    }
+
+    /// create benchmark, there is no object behind it.
+    pub fn create_benchmark(&mut self, cnode_cap: &mut CNodeCap) {
+        let cap = BenchmarkCap::init(0.into(), 0);
+        cnode_cap.write_slot(cap, ROOT_BENCHMARK_IDX);
+    }
+
     /// create address space of initial server.
     pub fn create_address_space(
         &mut self,
         cnode_cap: &mut CNodeCap,
+        asid_pool_cap: &mut AsidPoolCap,
         elf_header: *const Elf64Hdr,
         root_rsc_mgr: &mut RootServerResourceManager,
     ) -> (PageTableCap, VirtAddr) {
@@ -112,7 +159,7 @@ impl<'a> RootServerMemory<'a> {
 
         let vaddr = (root_page_table as *const PageTable).into();
         let mut cap = PageTableCap::init(vaddr, 0);
-        cap.make_as_root().unwrap();
+        cap.make_as_root(asid_pool_cap).unwrap();
         cnode_cap.write_slot(cap.replicate(), ROOT_VSPACE_IDX);
         let mut mapper = RootServerElfMapper::new(root_rsc_mgr, &mut cap, cnode_cap);
         unsafe {
@@ -167,6 +214,32 @@ impl<'a> RootServerMemory<'a> {
         (page_cap, vaddr)
     }
 
+    /// create read only frames of trace buffer, and return index of the first one.
+    #[cfg(feature = "trace")]
+    pub fn create_trace_buffer_frames(
+        &mut self,
+        cnode_cap: &mut CNodeCap,
+        bootstage_mbr: &mut RootServerResourceManager,
+    ) -> usize {
+        let buffer: *mut TraceBuffer = self.trace_buf.into();
+        unsafe {
+            ptr::write_bytes(buffer, 0, 1);
+            init_trace(&mut *buffer);
+        }
+        let first_idx = bootstage_mbr.alloc_cnode_idx();
+        for page_idx in 0..TRACE_BUFFER_PAGES {
+            let idx = if page_idx == 0 {
+                first_idx
+            } else {
+                bootstage_mbr.alloc_cnode_idx()
+            };
+            let mut page_cap = PageCap::init(self.trace_buf.add(PAGE_SIZE * page_idx), PAGE_SIZE);
+            page_cap.set_rights(PAGE_RIGHT_R);
+            cnode_cap.write_slot(page_cap, idx);
+        }
+        first_idx
+    }
+
     pub fn create_root_tcb(
         &mut self,
         cnode_cap: &mut CNodeCap,
@@ -181,7 +254,9 @@ impl<'a> RootServerMemory<'a> {
         tcb.registers.sepc = entry_point.into();
 
         // insert cnode_cap into tcb cnode_cap
-        let mut new_entry = CNodeEntry::new_with_rawcap(cnode_cap.replicate());
+        let new_entry = tcb
+            .root_cnode
+            .insert(CNodeEntry::new_with_rawcap(cnode_cap.replicate()));
         new_entry.insert(
             cnode_cap
                 .lookup_entry_mut_one_level(ROOT_CNODE_IDX)
@@ -189,9 +264,10 @@ impl<'a> RootServerMemory<'a> {
                 .as_mut()
                 .unwrap(),
         );
-        tcb.root_cnode = Some(new_entry);
         // insert vspace cap into tcb vspace
-        let mut new_entry = CNodeEntry::new_with_rawcap(vspace_cap.replicate());
+        let new_entry = tcb
+            .vspace
+            .insert(CNodeEntry::new_with_rawcap(vspace_cap.replicate()));
         new_entry.insert(
             cnode_cap
                 .lookup_entry_mut_one_level(ROOT_VSPACE_IDX)
@@ -199,8 +275,9 @@ impl<'a> RootServerMemory<'a> {
                 .as_mut()
                 .unwrap(),
         );
-        tcb.vspace = Some(new_entry);
-        let mut new_entry = CNodeEntry::new_with_rawcap(ipc_buf_cap.replicate());
+        let new_entry = tcb
+            .ipc_buffer
+            .insert(CNodeEntry::new_with_rawcap(ipc_buf_cap.replicate()));
         new_entry.insert(
             cnode_cap
                 .lookup_entry_mut_one_level(ROOT_IPC_BUFFER)
@@ -208,7 +285,6 @@ impl<'a> RootServerMemory<'a> {
                 .as_mut()
                 .unwrap(),
         );
-        tcb.ipc_buffer = Some(new_entry);
 
         let cap = TCBCap::init((tcb as *const ThreadControlBlock).into(), 0);
         cnode_cap.write_slot(cap.replicate(), ROOT_TCB_IDX);
@@ -367,7 +443,8 @@ fn create_mapped_page_cap(
     vaddr_n: VirtAddr,
     flags: usize,
 ) -> PageCap {
-    let mut page_cap = PageCap::init(paddr, 0);
+    let mut page_cap = PageCap::init(paddr, PAGE_SIZE);
+    page_cap.set_rights(PAGE_RIGHT_ALL);
     if let Err(e) = page_cap.map(root_table_cap, vaddr_n, flags) {
         match e.e_kind {
             ErrKind::PageTableNotMappedYet => {
@@ -1,21 +1,19 @@
-use crate::address::{KernelVAddress, PhysAddr, VirtAddr};
-use crate::common::align_down;
+use crate::address::{KernelVAddress, PhysAddr, VirtAddr, PAGE_LEVELS, PAGE_SIZE};
+use crate::init::pm::BumpAllocator;
+use crate::log_debug;
 use crate::memlayout::KERNEL_CODE_PFX;
-use crate::object::page_table::{KERNEL_VM_ROOT, LV2TABLE};
+use crate::object::page_table::{Pte, KERNEL_VM_ROOT};
 use crate::object::PageTable;
-use crate::println;
+use crate::stack::{guard_page, MAX_HARTS};
 
 use core::ptr;
 
-fn flag(leaf: bool) -> usize {
-    if leaf {
-        //    swdaguxwrv
-        0b0011101111
-    } else {
-        //    swdaguxwrv
-        0b0000000001
-    }
-}
+//                         swdaguxwrv
+const NON_LEAF: usize = 0b0000000001;
+const PHYSMAP: usize = 0b0011100111;
+const TEXT: usize = 0b0001101001;
+const RODATA: usize = 0b0001100011;
+const DATA: usize = 0b0011100111;
 
 extern "C" {
     static __text: u8;
@@ -24,41 +22,74 @@ extern "C" {
     static __data_end: u8;
     static __rodata: u8;
     static __rodata_end: u8;
+    static __stack_guard: u8;
+}
+
+/// Walk kernel root page table down to level 0, and return the entry for vaddr.
+/// Missing tables are allocated from bump allocator.
+/// Allocated tables are accessed by physical address, so identity mapping made by boot.S must be alive.
+unsafe fn walk_or_alloc(bump_allocator: &mut BumpAllocator, vaddr: VirtAddr) -> &mut Pte {
+    let mut table = &raw mut KERNEL_VM_ROOT;
+    for level in (1..PAGE_LEVELS).rev() {
+        let pte = &mut (*table)[vaddr.get_vpn(level)];
+        if !pte.is_valid() {
+            pte.write(bump_allocator.allocate_page(), NON_LEAF);
+        }
+        table = pte.get_address().addr as *mut PageTable;
+    }
+    &mut (*table)[vaddr.get_vpn(0)]
+}
+
+/// Map [start, end) of kernel image with 4KiB pages.
+unsafe fn map_kernel_section(
+    bump_allocator: &mut BumpAllocator,
+    start: usize,
+    end: usize,
+    flags: usize,
+) {
+    for vaddr in (start..end).step_by(PAGE_SIZE) {
+        let vaddr: VirtAddr = vaddr.into();
+        let paddr: PhysAddr = vaddr.bit_and(!KERNEL_CODE_PFX).into();
+        walk_or_alloc(bump_allocator, vaddr).write(paddr, flags);
+    }
 }
 
 /// This function must be called only once at initialization.
 /// After this function, we cannot access physical memory directly.
-pub unsafe fn kernel_vm_init(free_ram_end_phys: usize) {
+pub unsafe fn kernel_vm_init(bump_allocator: &mut BumpAllocator, free_ram_end_phys: usize) {
     let phyisical_start: usize = 0;
     let phyisical_end: usize = free_ram_end_phys;
-    let kerenel_txt = ptr::addr_of!(__text) as usize;
-    let kernel_data_end = ptr::addr_of!(__data_end) as usize;
+    let root_level = PAGE_LEVELS - 1;
 
-    // first, mappin all physical memory
-    let step: usize = 2_usize.pow(9 + 9 + 9 + 12);
+    // first, mappin all physical memory with leaves of root.
+    // this window is only for data, so it is not executable.
+    let step: usize = 1 << (12 + 9 * root_level);
     for paddr in (phyisical_start..phyisical_end).step_by(step) {
         let paddr: PhysAddr = paddr.into();
         let vaddr: VirtAddr = KernelVAddress::from(paddr).into();
-        let vpn = vaddr.get_vpn(3);
+        let vpn = vaddr.get_vpn(root_level);
         let pte = &mut KERNEL_VM_ROOT[vpn];
-        pte.write(paddr, flag(true));
+        pte.write(paddr, PHYSMAP);
     }
-    let vpn = VirtAddr::from(kerenel_txt).get_vpn(3);
-    let lv2_addr = PhysAddr::from(&raw const LV2TABLE);
-    let pte = &mut KERNEL_VM_ROOT[vpn];
-    pte.write(lv2_addr.bit_and(!KERNEL_CODE_PFX), flag(false));
 
-    // mapping elf;
-    let step = 2_usize.pow(9 + 9 + 12);
-    let elf_v_start = align_down(kerenel_txt, step);
-    for vaddr in (elf_v_start..kernel_data_end).step_by(step) {
-        let vaddr: VirtAddr = vaddr.into();
-        let paddr: PhysAddr = vaddr.bit_and(!KERNEL_CODE_PFX).into();
-        let vpn = vaddr.get_vpn(2);
-        let pte = &mut LV2TABLE[vpn];
-        pte.write(paddr, flag(true));
+    // mapping elf, each section has its own permission.
+    let text = ptr::addr_of!(__text) as usize;
+    let text_end = ptr::addr_of!(__text_end) as usize;
+    let rodata = ptr::addr_of!(__rodata) as usize;
+    let rodata_end = ptr::addr_of!(__rodata_end) as usize;
+    let data = ptr::addr_of!(__data) as usize;
+    let data_end = ptr::addr_of!(__data_end) as usize;
+    let stack_guard = ptr::addr_of!(__stack_guard) as usize;
+    map_kernel_section(bump_allocator, text, text_end, TEXT);
+    map_kernel_section(bump_allocator, rodata, rodata_end, RODATA);
+    // guard page is left unmapped, so that stack overflow causes page fault.
+    map_kernel_section(bump_allocator, data, stack_guard, DATA);
+    map_kernel_section(bump_allocator, stack_guard + PAGE_SIZE, data_end, DATA);
+    // kernel stacks of each hart are in .bss, so unmap their guard pages.
+    for hartid in 0..MAX_HARTS {
+        walk_or_alloc(bump_allocator, guard_page(hartid).into()).clear();
     }
 
     PageTable::activate_kernel_table();
-    println!("root vm activation finished");
+    log_debug!("root vm activation finished");
 }
@@ -7,22 +7,32 @@
 mod address;
 mod capability;
 pub mod common;
+#[cfg(feature = "debug-syscalls")]
+mod debug;
+mod fpu;
 mod handler;
 mod init;
 pub mod list;
+pub mod log;
 mod memlayout;
 mod object;
+mod pmu;
 mod riscv;
 mod sbi;
 mod scheduler;
+mod stack;
 mod syscall;
 mod timer;
+#[cfg(feature = "trace")]
+mod trace;
 pub mod uart;
 
+pub use address::PAGE_LEVELS;
 pub use capability::CapabilityType;
 pub use common::{ErrKind, KernelError, KernelResult};
 pub use handler::return_to_user;
 pub use init::init_kernel;
+pub use object::page_table::SATP_MODE;
 pub use object::Registers;
 pub use syscall::InvLabel;
 pub use syscall::SysCallNo;
@@ -62,7 +62,40 @@ impl<T> LinkedList<T> {
         }
 
         self.head = next;
-        result.map(|ptr| unsafe { &mut *ptr.as_ptr() })
+        result.map(|ptr| unsafe {
+            let item = &mut *ptr.as_ptr();
+            item.next = None;
+            item
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ListItem<T>> {
+        core::iter::successors(self.head, |ptr| unsafe { ptr.as_ref().next })
+            .map(|ptr| unsafe { &*ptr.as_ptr() })
+    }
+
+    /// Take item out from anywhere of the list.
+    /// Returns false if the item is not in the list.
+    pub fn remove(&mut self, item: &mut ListItem<T>) -> bool {
+        let target = item as *mut ListItem<T>;
+        let mut prev: Option<NonNull<ListItem<T>>> = None;
+        let mut cur = self.head;
+        while let Some(cur_ptr) = cur {
+            if cur_ptr.as_ptr() == target {
+                let next = item.next.take();
+                match prev {
+                    Some(mut prev_ptr) => unsafe { prev_ptr.as_mut().next = next },
+                    None => self.head = next,
+                }
+                if self.last == Some(cur_ptr) {
+                    self.last = prev;
+                }
+                return true;
+            }
+            prev = cur;
+            cur = unsafe { cur_ptr.as_ref().next };
+        }
+        false
     }
 }
 
@@ -0,0 +1,88 @@
+use core::fmt;
+
+use crate::scheduler::get_hartid;
+
+#[cfg(any(
+    all(feature = "log-error", feature = "log-warn"),
+    all(feature = "log-error", feature = "log-info"),
+    all(feature = "log-error", feature = "log-debug"),
+    all(feature = "log-error", feature = "log-trace"),
+    all(feature = "log-warn", feature = "log-info"),
+    all(feature = "log-warn", feature = "log-debug"),
+    all(feature = "log-warn", feature = "log-trace"),
+    all(feature = "log-info", feature = "log-debug"),
+    all(feature = "log-info", feature = "log-trace"),
+    all(feature = "log-debug", feature = "log-trace"),
+))]
+compile_error!("only one of features \"log-error\", \"log-warn\", \"log-info\", \"log-debug\" and \"log-trace\" can be enabled");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Most verbose level which is logged, info is used when none of log features is enabled.
+#[cfg(feature = "log-error")]
+pub const MAX_LEVEL: Level = Level::Error;
+#[cfg(feature = "log-warn")]
+pub const MAX_LEVEL: Level = Level::Warn;
+#[cfg(feature = "log-debug")]
+pub const MAX_LEVEL: Level = Level::Debug;
+#[cfg(feature = "log-trace")]
+pub const MAX_LEVEL: Level = Level::Trace;
+#[cfg(not(any(
+    feature = "log-error",
+    feature = "log-warn",
+    feature = "log-debug",
+    feature = "log-trace"
+)))]
+pub const MAX_LEVEL: Level = Level::Info;
+
+/// Compared with constant, so that disabled logs are removed at compile time.
+#[inline(always)]
+pub const fn enabled(level: Level) -> bool {
+    level as usize <= MAX_LEVEL as usize
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, args: fmt::Arguments) {
+    crate::println!("[{:?} hart {}] {}", level, get_hartid(), args);
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::log::enabled($level) {
+            $crate::log::_log($level, format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Error, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Warn, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Info, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Debug, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Trace, $($arg)*));
+}
@@ -6,8 +6,10 @@ use core::{panic::PanicInfo, ptr};
 
 use core::arch::global_asm;
 use kernel::init_kernel;
-use kernel::println;
 use kernel::return_to_user;
+use kernel::uart::panic_print;
+use kernel::{log_debug, log_info};
+use kernel::{PAGE_LEVELS, SATP_MODE};
 use shared::aligned_to::AlignedTo;
 use shared::elf::def::Elf64Hdr;
 
@@ -17,7 +19,11 @@ extern "C" {
     static __stack_top: u8;
 }
 
-global_asm!(include_str!("boot.S"));
+global_asm!(
+    include_str!("boot.S"),
+    levels = const PAGE_LEVELS,
+    satp_mode = const SATP_MODE
+);
 
 static ALIGNED: &AlignedTo<u8, [u8]> = &AlignedTo {
     _align: [],
@@ -39,16 +45,16 @@ extern "C" fn kernel_main(
         let bss_end = ptr::addr_of!(__bss_end);
         ptr::write_bytes(bss, 0, bss_end as usize - bss as usize);
     };
-    println!("cpu id is {}", hartid);
+    log_info!("cpu id is {}", hartid);
     let elf_header = (ROOTSERVER as *const [u8]).cast::<Elf64Hdr>();
 
-    init_kernel(elf_header, free_ram_phys, free_ram_end_phys);
-    println!("return to user");
+    init_kernel(hartid, elf_header, free_ram_phys, free_ram_end_phys);
+    log_debug!("return to user");
     unsafe { return_to_user() }
 }
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
+    panic_print(format_args!("{}\n", info));
     loop {}
 }
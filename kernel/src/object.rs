@@ -1,18 +1,30 @@
+pub mod asid_pool;
 mod cnode;
 mod endpoint;
 mod notification;
 pub mod page_table;
 mod tcb;
 
-pub use crate::object::cnode::{CNode, CNodeEntry, CSlot, ManagementDB};
+pub use crate::object::asid_pool::{AsidControl, AsidPool};
+pub use crate::object::cnode::{move_entries, CNode, CNodeEntry, CSlot, ManagementDB};
 pub use crate::object::endpoint::Endpoint;
 pub use crate::object::notification::Notification;
 pub use crate::object::page_table::get_user_flags;
 pub use crate::object::page_table::PageTable;
-pub use crate::object::tcb::{resume, Register, Registers, ThreadControlBlock, ThreadInfo};
+pub use crate::object::tcb::{
+    resume, suspend, Register, Registers, ThreadControlBlock, ThreadInfo, ThreadState,
+};
 
 pub struct Untyped;
 
+// marker of SchedControl capability, scheduler itself is behind it.
+#[derive(Debug)]
+pub struct SchedControl;
+
+// marker of Benchmark capability, performance counters of hart are behind it.
+#[derive(Debug)]
+pub struct Benchmark;
+
 // marker trait
 pub trait KObject {}
 
@@ -0,0 +1,117 @@
+use crate::common::{ErrKind, KernelResult};
+use crate::kerr;
+
+use super::PageTable;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/*
+ * ASID
+ * | high 5 (index of ASID_TABLE) | low 9 (index in AsidPool) |
+ * 14                                                        0
+ */
+pub const ASID_LOW_BITS: usize = 9;
+pub const ASID_HIGH_BITS: usize = 5;
+pub const ASID_BITS: usize = ASID_LOW_BITS + ASID_HIGH_BITS;
+const ASID_POOL_SIZE: usize = 1 << ASID_LOW_BITS;
+const ASID_TABLE_SIZE: usize = 1 << ASID_HIGH_BITS;
+
+// number of asid bits implemented by hardware, which is probed at boot.
+static ASID_LEN: AtomicUsize = AtomicUsize::new(ASID_BITS);
+
+pub fn set_asid_len(asid_len: usize) {
+    ASID_LEN.store(asid_len, Ordering::Relaxed);
+}
+
+/// Without hardware asid, every address space uses asid 0 and tlb is flushed on switch.
+pub fn has_hw_asid() -> bool {
+    ASID_LEN.load(Ordering::Relaxed) != 0
+}
+
+/// Asid written to satp and used by sfence.vma.
+pub fn hw_asid(asid: usize) -> usize {
+    if has_hw_asid() {
+        asid
+    } else {
+        0
+    }
+}
+
+/// Asids must be below this, or they alias in hardware.
+fn asid_limit() -> usize {
+    let asid_len = ASID_LEN.load(Ordering::Relaxed);
+    if asid_len == 0 {
+        1 << ASID_BITS
+    } else {
+        1 << asid_len.min(ASID_BITS)
+    }
+}
+
+// TODO: use once cell
+static mut ASID_TABLE: [Option<NonNull<AsidPool>>; ASID_TABLE_SIZE] = [None; ASID_TABLE_SIZE];
+
+// marker of AsidControl capability, there is no object behind it.
+#[derive(Debug)]
+pub struct AsidControl;
+
+// ASID 0 is reserved for kernel page table,
+// so the first entry of the pool whose base is 0 will never be used.
+#[repr(align(4096))]
+#[derive(Debug)]
+pub struct AsidPool([Option<NonNull<PageTable>>; ASID_POOL_SIZE]);
+
+impl AsidPool {
+    pub const fn new() -> Self {
+        Self([None; ASID_POOL_SIZE])
+    }
+
+    pub fn assign(&mut self, asid_base: usize, table: &mut PageTable) -> KernelResult<usize> {
+        let start = if asid_base == 0 { 1 } else { 0 };
+        let (idx, entry) = self
+            .0
+            .iter_mut()
+            .enumerate()
+            .skip(start)
+            .take_while(|(idx, _)| asid_base | idx < asid_limit())
+            .find(|(_, entry)| entry.is_none())
+            .ok_or(kerr!(ErrKind::NoEnoughSlot))?;
+        *entry = Some(NonNull::from(table));
+        Ok(asid_base | idx)
+    }
+}
+
+impl Default for AsidPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Return asid base of the first unused entry of ASID_TABLE.
+#[allow(static_mut_refs)]
+pub fn find_free_asid_base() -> KernelResult<usize> {
+    unsafe { ASID_TABLE.iter() }
+        .position(|pool| pool.is_none())
+        .map(|high| high << ASID_LOW_BITS)
+        .filter(|asid_base| *asid_base < asid_limit())
+        .ok_or(kerr!(ErrKind::NoEnoughSlot))
+}
+
+#[allow(static_mut_refs)]
+pub fn register_asid_pool(asid_base: usize, pool: &mut AsidPool) -> KernelResult<()> {
+    let entry = unsafe { &mut ASID_TABLE[asid_base >> ASID_LOW_BITS] };
+    entry
+        .is_none()
+        .then_some(())
+        .ok_or(kerr!(ErrKind::NotEmptySlot))?;
+    *entry = Some(NonNull::from(pool));
+    Ok(())
+}
+
+/// Return root page table which was assigned given asid.
+pub fn find_vspace_for_asid<'a>(asid: usize) -> KernelResult<&'a mut PageTable> {
+    let high = (asid >> ASID_LOW_BITS) & (ASID_TABLE_SIZE - 1);
+    let low = asid & (ASID_POOL_SIZE - 1);
+    let mut pool = unsafe { ASID_TABLE[high] }.ok_or(kerr!(ErrKind::NotRootPageTable))?;
+    let mut table = unsafe { pool.as_mut().0[low] }.ok_or(kerr!(ErrKind::NotRootPageTable))?;
+    unsafe { Ok(table.as_mut()) }
+}
@@ -1,14 +1,15 @@
 use crate::{
     address::{KernelVAddress, PhysAddr},
     capability::{CapInSlot, Capability, CapabilityData, Something},
-    common::KernelResult,
-    CapabilityType,
+    common::{ErrKind, KernelResult},
+    kerr, CapabilityType,
 };
-use core::{fmt::Debug, mem};
+use core::{fmt::Debug, mem, ptr};
 use shared::const_assert;
 
 use super::{
-    page_table::Page, Endpoint, KObject, Notification, PageTable, ThreadControlBlock, Untyped,
+    page_table::Page, AsidControl, AsidPool, Benchmark, Endpoint, KObject, Notification, PageTable,
+    SchedControl, ThreadControlBlock, Untyped,
 };
 
 /*
@@ -78,6 +79,41 @@ impl ManagementDB {
 
 pub type CSlot<T = Something> = Option<CNodeEntry<T>>;
 
+/// Move entries of (source, destination) slots at once, so that caps can be swapped or rotated.
+/// Destinations must be empty or one of sources, and entries keep their place in ManagementDB.
+pub unsafe fn move_entries<const N: usize>(moves: [(*mut CSlot, *mut CSlot); N]) {
+    // entry is at the same address of slot thanks to niche of cap_type.
+    let relocate = |node: Option<&mut CNodeEntry<Something>>| {
+        node.map(|node| {
+            let node = node as *mut CNodeEntry<Something>;
+            moves
+                .iter()
+                .find(|(src, _)| ptr::eq(src.cast(), node))
+                .map_or(node, |(_, dest)| dest.cast())
+        })
+    };
+    // neighbours have to be resolved before any slot is overwritten,
+    // because a destination can be a source of other move.
+    let links = moves.map(|(src, _)| {
+        let entry = (*src).as_mut().unwrap();
+        (relocate(entry.get_prev()), relocate(entry.get_next()))
+    });
+    let entries = moves.map(|(src, _)| (*src).take().unwrap());
+    for (mut entry, (_, dest)) in entries.into_iter().zip(moves) {
+        entry.mdb = ManagementDB::default();
+        *dest = Some(entry);
+    }
+    for ((prev, next), (_, dest)) in links.into_iter().zip(moves) {
+        let entry = (*dest).as_mut().unwrap();
+        if let Some(prev) = prev {
+            prev.as_mut().unwrap().set_next(entry);
+        }
+        if let Some(next) = next {
+            entry.set_next(next.as_mut().unwrap());
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CNodeEntry<K: KObject>
 where
@@ -134,7 +170,41 @@ impl CNodeEntry<Something> {
                 let page_table = unsafe { self.cap.unchecked_ref_as::<PageTable>() };
                 page_table.derive(self).map(Into::into)
             }
+            CapabilityType::AsidControl => {
+                let asid_control = unsafe { self.cap.unchecked_ref_as::<AsidControl>() };
+                asid_control.derive(self).map(Into::into)
+            }
+            CapabilityType::AsidPool => {
+                let asid_pool = unsafe { self.cap.unchecked_ref_as::<AsidPool>() };
+                asid_pool.derive(self).map(Into::into)
+            }
+            CapabilityType::Benchmark => {
+                let benchmark = unsafe { self.cap.unchecked_ref_as::<Benchmark>() };
+                benchmark.derive(self).map(Into::into)
+            }
+            CapabilityType::SchedControl => {
+                let sched_control = unsafe { self.cap.unchecked_ref_as::<SchedControl>() };
+                sched_control.derive(self).map(Into::into)
+            }
+        }
+    }
+
+    /// derive and modify capability with given value.
+    /// other caps keep object state in raw data, so value is not accepted for them.
+    pub fn mint(&self, value: usize) -> KernelResult<CapInSlot> {
+        let mut cap = self.derive()?;
+        match cap.get_cap_type()? {
+            CapabilityType::Page => cap.try_ref_mut_as::<Page>()?.mint(value),
+            CapabilityType::CNode => cap.try_ref_mut_as::<CNode>()?.set_guard(value)?,
+            CapabilityType::EndPoint => {
+                cap.try_ref_mut_as::<Endpoint>()?.set_badge(value as u64)?
+            }
+            CapabilityType::Notification => cap
+                .try_ref_mut_as::<Notification>()?
+                .set_badge(value as u64)?,
+            _ => return Err(kerr!(ErrKind::InvalidOperation)),
         }
+        Ok(cap)
     }
 }
 
@@ -226,16 +296,16 @@ impl CNode {
         index: usize,
     ) {
         let root = (self as *mut Self).cast::<CNodeEntry<Something>>();
-        let mut entry = CNodeEntry {
-            cap: cap.into(),
-            mdb: ManagementDB::default(),
+        let entry = unsafe {
+            root.add(index).write(CNodeEntry {
+                cap: cap.into(),
+                mdb: ManagementDB::default(),
+            });
+            root.add(index).as_mut().unwrap()
         };
         if let Some(prev_next) = parent.get_next() {
             entry.set_next(prev_next);
         };
-        parent.set_next(&mut entry);
-        unsafe {
-            *root.add(index) = entry;
-        }
+        parent.set_next(entry);
     }
 }
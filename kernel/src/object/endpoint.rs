@@ -1,6 +1,8 @@
+use core::ptr::NonNull;
+
 use crate::list::LinkedList;
 
-use super::tcb::{resume, ThreadControlBlock, ThreadInfo};
+use super::tcb::{resume, ThreadControlBlock, ThreadInfo, WaitQueue};
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum EndpointState {
@@ -48,8 +50,7 @@ impl Endpoint {
             wake_up_thread(reciever_thread);
             false
         } else {
-            block_thread(thread);
-            self.queue.push(thread);
+            self.block(thread);
             true
         }
     }
@@ -61,12 +62,25 @@ impl Endpoint {
             wake_up_thread(send_thread);
             false
         } else {
-            block_thread(thread);
-            self.queue.push(thread);
+            self.block(thread);
             true
         }
     }
 
+    fn block(&mut self, thread: &mut ThreadControlBlock) {
+        block_thread(thread);
+        thread.waiting_on = WaitQueue::Endpoint(NonNull::from(&mut *self));
+        self.queue.push(thread);
+    }
+
+    /// Remove thread which is suspended while waiting.
+    pub fn cancel(&mut self, thread: &mut ThreadControlBlock) {
+        self.queue.remove(thread);
+        if self.queue.is_empty() {
+            self.ep_state = EndpointState::Idel;
+        }
+    }
+
     fn is_idle(&self) -> bool {
         self.ep_state == EndpointState::Idel
     }
@@ -80,6 +94,7 @@ impl Default for Endpoint {
 
 fn wake_up_thread(tcb: &mut ThreadControlBlock) {
     assert!(tcb.next_is_none());
+    tcb.waiting_on = WaitQueue::None;
     resume(tcb);
 }
 
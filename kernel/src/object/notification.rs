@@ -1,7 +1,10 @@
+use core::ptr::NonNull;
+
 use crate::list::LinkedList;
 
-use super::tcb::{resume, ThreadControlBlock, ThreadInfo};
+use super::tcb::{resume, ThreadControlBlock, ThreadInfo, WaitQueue};
 
+#[derive(Debug)]
 pub struct Notification {
     notify_bit: u64,
     wait_queue: LinkedList<ThreadInfo>,
@@ -41,10 +44,16 @@ impl Notification {
             false
         } else {
             block_thread(thread);
+            thread.waiting_on = WaitQueue::Notification(NonNull::from(&mut *self));
             self.wait_queue.push(thread);
             true
         }
     }
+
+    /// Remove thread which is suspended while waiting.
+    pub fn cancel(&mut self, thread: &mut ThreadControlBlock) {
+        self.wait_queue.remove(thread);
+    }
 }
 
 impl Default for Notification {
@@ -55,6 +64,7 @@ impl Default for Notification {
 
 fn wake_up_thread(tcb: &mut ThreadControlBlock) {
     assert!(tcb.next_is_none());
+    tcb.waiting_on = WaitQueue::None;
     resume(tcb);
 }
 
@@ -1,5 +1,6 @@
 /*
- Implement sv48;
+ Implement sv39, sv48 and sv57, selected by feature, (below is sv48).
+ sv39 doesn't have VPN[3] and sv57 has VPN[4] over VPN[3].
 
 virtual address
  47        39  38       30  29       21  20       12  11               0
@@ -24,12 +25,16 @@ page table entry
  */
 
 use crate::{
-    address::{KernelVAddress, PhysAddr, VirtAddr, PAGE_SIZE},
+    address::{KernelVAddress, PhysAddr, VirtAddr, PAGE_LEVELS, PAGE_SIZE},
     common::{ErrKind, KernelResult},
     kerr,
     memlayout::KERNEL_CODE_PFX,
+    object::asid_pool::{has_hw_asid, hw_asid},
+    riscv::{r_satp, sfence, w_satp},
 };
 
+use shared::page_size::PageSize;
+
 use core::{
     arch::asm,
     ops::{Deref, DerefMut},
@@ -37,22 +42,66 @@ use core::{
 };
 
 // From unix style to riscv
+// | memory type 2 | reserved 1 | read 1 | write 1 | exec 1 |
+// global bit is never set, because global tlb entry ignores asid and is seen by other vspaces.
 pub fn get_user_flags(flags: usize) -> usize {
-    (if (flags & 0x1) == 0x1 { PAGE_X } else { 0x0 }
+    let rwx = if (flags & 0x1) == 0x1 { PAGE_X } else { 0x0 }
         | if (flags & 0x2) == 0x2 { PAGE_W } else { 0x0 }
-        | if (flags & 0x4) == 0x4 { PAGE_R } else { 0x0 })
+        | if (flags & 0x4) == 0x4 { PAGE_R } else { 0x0 };
+    // preset accessed and dirty bit, not to cause page fault on hardware which doesn't update them.
+    let dirty = if (rwx & PAGE_W) == PAGE_W {
+        PAGE_D
+    } else {
+        0x0
+    };
+    let ad = PAGE_A | dirty;
+    rwx | ad | get_memory_type((flags >> 4) & 0x3)
+}
+
+#[cfg(feature = "svpbmt")]
+fn get_memory_type(memory_type: usize) -> usize {
+    match memory_type {
+        1 => PAGE_PBMT_NC,
+        2 => PAGE_PBMT_IO,
+        _ => 0x0,
+    }
 }
 
-pub const SATP_SV48: usize = 9 << 60;
+// without svpbmt, attributes are decided by PMA of physical address.
+#[cfg(not(feature = "svpbmt"))]
+fn get_memory_type(_memory_type: usize) -> usize {
+    0x0
+}
+
+// sv39: 8, sv48: 9, sv57: 10
+pub const SATP_MODE: usize = (PAGE_LEVELS + 5) << 60;
+pub const SATP_ASID_SHIFT: usize = 44;
+const SATP_ASID_MASK: usize = 0xffff;
 pub const PAGE_V: usize = 1 << 0;
 pub const PAGE_R: usize = 1 << 1;
 pub const PAGE_W: usize = 1 << 2;
 pub const PAGE_X: usize = 1 << 3;
 pub const PAGE_U: usize = 1 << 4;
+pub const PAGE_A: usize = 1 << 6;
+pub const PAGE_D: usize = 1 << 7;
+#[cfg(feature = "svpbmt")]
+pub const PAGE_PBMT_NC: usize = 1 << 61;
+#[cfg(feature = "svpbmt")]
+pub const PAGE_PBMT_IO: usize = 2 << 61;
+const PTE_PPN_MASK: usize = (1 << 44) - 1;
 
 // TODO: use once cell
 pub static mut KERNEL_VM_ROOT: PageTable = PageTable::new();
-pub static mut LV2TABLE: PageTable = PageTable::new();
+
+/// Entry without R and X is a pointer to next table, and W without R is reserved.
+/// So user flags must be checked, or user frame can be walked as a page table.
+fn check_leaf_flags(flags: usize) -> KernelResult<()> {
+    let is_leaf = flags & (PAGE_R | PAGE_X) != 0;
+    let is_reserved = flags & (PAGE_R | PAGE_W) == PAGE_W;
+    (is_leaf && !is_reserved)
+        .then_some(())
+        .ok_or(kerr!(ErrKind::InvalidOperation))
+}
 
 // page table lv1(bottom) has 512 * 4kb page = 2048kb
 // page table lv2(middle) has 512 * lv1 table = 512 * 2048kb
@@ -68,8 +117,8 @@ impl PageTable {
         Self([Pte(0); 512])
     }
     pub fn map(&self, parent: &mut Self, vaddr: VirtAddr) -> KernelResult<usize> {
-        let (level, entry) = parent.walk(vaddr);
-        if level == 0 {
+        let (level, entry) = parent.walk(vaddr, 0);
+        if level == 0 || entry.is_valid() {
             Err(kerr!(ErrKind::VaddressAlreadyMapped))
         } else {
             entry.write(KernelVAddress::from(self as *const _), PAGE_V);
@@ -77,30 +126,51 @@ impl PageTable {
         }
     }
 
-    pub fn walk(&mut self, vaddr: VirtAddr) -> (usize, &mut Pte) {
+    /// Walk page table until target level.
+    /// Returned entry is at target level, otherwise it is invalid or leaf entry at upper level.
+    pub fn walk(&mut self, vaddr: VirtAddr, target_level: usize) -> (usize, &mut Pte) {
         let mut page_table = self;
         // walk page table
-        for level in (1..=3).rev() {
+        for level in ((target_level + 1)..PAGE_LEVELS).rev() {
             let vpn = vaddr.get_vpn(level);
             let pte = &mut page_table[vpn];
-            if !pte.is_valid() {
+            if !pte.is_valid() || pte.is_leaf() {
                 return (level, pte);
             }
             page_table = pte.as_page_table();
         }
 
-        let pte = &mut page_table[vaddr.get_vpn(0)];
-        (0, pte)
+        let pte = &mut page_table[vaddr.get_vpn(target_level)];
+        (target_level, pte)
     }
 
-    pub unsafe fn activate(&self) {
+    // tlb entries are tagged with asid, so no need to flush on switching address space
+    // unless hardware doesn't implement asid.
+    pub unsafe fn activate(&self, asid: usize) {
         let addr: PhysAddr = KernelVAddress::from(self as *const Self).into();
-        asm!(
-            "sfence.vma x0, x0",
-            "csrw satp, {satp}",
-            "sfence.vma x0, x0",
-            satp = in(reg) (SATP_SV48 | (addr.addr >> 12))
-        )
+        let satp = SATP_MODE | (hw_asid(asid) << SATP_ASID_SHIFT) | (addr.addr >> 12);
+        if has_hw_asid() {
+            asm!("csrw satp, {satp}", satp = in(reg) satp)
+        } else {
+            asm!(
+                "sfence.vma x0, x0",
+                "csrw satp, {satp}",
+                "sfence.vma x0, x0",
+                satp = in(reg) satp
+            )
+        }
+    }
+
+    /// Return number of asid bits implemented by hardware.
+    /// Unimplemented bits of satp.ASID are read as zero after writing all ones.
+    pub unsafe fn probe_asid_len() -> usize {
+        let satp = r_satp();
+        w_satp(satp | (SATP_ASID_MASK << SATP_ASID_SHIFT));
+        let asid_len = ((r_satp() >> SATP_ASID_SHIFT) & SATP_ASID_MASK).count_ones();
+        w_satp(satp);
+        // entries might be filled with the probed asid.
+        sfence();
+        asid_len as usize
     }
 
     pub fn copy_global_mapping(&mut self) {
@@ -118,7 +188,7 @@ impl PageTable {
                 "sfence.vma x0, x0",
                 "csrw satp, {satp}",
                 "sfence.vma x0, x0",
-                satp = in(reg) (SATP_SV48 | (address >> 12))
+                satp = in(reg) (SATP_MODE | (address >> 12))
             )
         }
     }
@@ -137,7 +207,7 @@ impl DerefMut for PageTable {
     }
 }
 
-// 4kb page
+// 4kb page, or head of 2mb/1gb page
 #[repr(align(4096))]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Page;
@@ -147,9 +217,16 @@ impl Page {
         Self
     }
 
-    pub fn map(&self, parent: &mut PageTable, vaddr: VirtAddr, flags: usize) -> KernelResult<()> {
-        let (level, entry) = parent.walk(vaddr);
-        if level != 0 {
+    pub fn map(
+        &self,
+        parent: &mut PageTable,
+        vaddr: VirtAddr,
+        flags: usize,
+        size: PageSize,
+    ) -> KernelResult<()> {
+        check_leaf_flags(flags)?;
+        let (level, entry) = parent.walk(vaddr, size.level());
+        if level != size.level() && !entry.is_valid() {
             Err(kerr!(ErrKind::PageTableNotMappedYet, level as u16))
         } else if entry.is_valid() {
             Err(kerr!(ErrKind::VaddressAlreadyMapped))
@@ -159,20 +236,48 @@ impl Page {
         }
     }
 
-    // FIXME: get page table from self,
-    // currently we can unmap another process page accidentally, which has same vaddr as self.
-    // consider using asid pool.
-    pub fn unmap(&mut self, parent: &mut PageTable, vaddr: VirtAddr) -> KernelResult<()> {
-        let (level, entry) = parent.walk(vaddr);
-        if level != 0 {
+    pub fn unmap(
+        &mut self,
+        parent: &mut PageTable,
+        vaddr: VirtAddr,
+        size: PageSize,
+    ) -> KernelResult<Pte> {
+        let entry = self.lookup_mapped_entry(parent, vaddr, size)?;
+        let old = *entry;
+        entry.clear();
+        Ok(old)
+    }
+
+    /// Rewrite flags of existing mapping, and return old entry.
+    pub fn remap(
+        &mut self,
+        parent: &mut PageTable,
+        vaddr: VirtAddr,
+        flags: usize,
+        size: PageSize,
+    ) -> KernelResult<Pte> {
+        check_leaf_flags(flags)?;
+        let entry = self.lookup_mapped_entry(parent, vaddr, size)?;
+        let old = *entry;
+        entry.write(entry.get_address(), flags | PAGE_V);
+        Ok(old)
+    }
+
+    fn lookup_mapped_entry<'a>(
+        &self,
+        parent: &'a mut PageTable,
+        vaddr: VirtAddr,
+        size: PageSize,
+    ) -> KernelResult<&'a mut Pte> {
+        let (level, entry) = parent.walk(vaddr, size.level());
+        if level != size.level() {
             Err(kerr!(ErrKind::PageTableNotMappedYet, level as u16))
         } else if !entry.is_valid() {
             Err(kerr!(ErrKind::PageNotMappedYet))
-        } else if KernelVAddress::from(self as *mut Page) != entry.get_address().into() {
+        } else if KernelVAddress::from(self as *const Page) != entry.get_address().into() {
             Err(kerr!(ErrKind::InvalidOperation))
         } else {
-            entry.clear();
-            Ok(())
+            Ok(entry)
         }
     }
 }
@@ -185,8 +290,18 @@ impl Pte {
         self.0 & PAGE_V != 0
     }
 
+    pub fn is_leaf(&self) -> bool {
+        self.0 & (PAGE_R | PAGE_W | PAGE_X) != 0
+    }
+
     pub fn get_address(&self) -> PhysAddr {
-        PhysAddr::from((self.0 << 2) & !0xfff)
+        PhysAddr::from(((self.0 >> 10) & PTE_PPN_MASK) << 12)
+    }
+
+    #[cfg(feature = "debug-syscalls")]
+    pub fn is_user_readable(&self) -> bool {
+        let flags = PAGE_V | PAGE_U | PAGE_R;
+        self.0 & flags == flags
     }
 
     pub fn write<A: Into<PhysAddr>>(&mut self, addr: A, flags: usize) {
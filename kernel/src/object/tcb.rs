@@ -1,40 +1,84 @@
 use crate::address::KernelVAddress;
+use crate::capability::notification::NotificationCap;
 use crate::capability::page_table::PageCap;
 use crate::capability::{cnode::CNodeCap, page_table::PageTableCap};
 use crate::common::{ErrKind, IPCBuffer, KernelResult};
 use crate::kerr;
 use crate::list::ListItem;
+use crate::log_warn;
 use crate::object::PageTable;
-use crate::println;
 
-use crate::scheduler::push;
-use core::ptr;
+use crate::scheduler::{get_current_tcb_mut, push, remove, require_schedule};
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use super::cnode::CNodeEntry;
 use super::page_table::Page;
-use super::{CNode, CSlot, KObject};
+use super::{CNode, CSlot, Endpoint, KObject, Notification};
+use shared::registers::FpRegisters;
 pub use shared::registers::Register;
 pub use shared::registers::Registers;
-#[cfg(debug_assertions)]
-static mut TCBIDX: usize = 0;
+
+#[cfg(feature = "debug-syscalls")]
+use crate::debug::ThreadName;
+#[cfg(feature = "vector")]
+use crate::fpu::vector::VectorRegisters;
+// 0 is for idle thread.
+static NEXT_TID: AtomicUsize = AtomicUsize::new(1);
 
 pub type ThreadControlBlock = ListItem<ThreadInfo>;
 
 impl KObject for ListItem<ThreadInfo> {}
 
 pub fn resume(thread: &mut ThreadControlBlock) {
+    // running thread or thread in runqueue must not be pushed twice.
+    if thread.is_runnable() {
+        return;
+    }
     thread.resume();
     push(thread)
 }
 
-#[allow(dead_code)]
-pub fn suspend(_thread: &mut ThreadControlBlock) {
-    // TODO: Impl Double linked list
-    // 1, check self status is Runnable.
-    // 2, if true, then self.next.prev = self.prev and self.prev.next = self.next
-    // (i.e take self out from runqueue)
-    // then call self.suspend()
-    todo!()
+/// Stop thread until it is resumed.
+/// Blocked thread is taken out of the queue it waits in, and its ipc fails with IpcCancelled.
+pub fn suspend(thread: &mut ThreadControlBlock) -> KernelResult<()> {
+    match thread.status {
+        ThreadState::Runnable => {
+            // running thread is not in runqueue.
+            if ptr::eq(thread, get_current_tcb_mut()) {
+                require_schedule();
+            } else {
+                remove(thread);
+            }
+        }
+        ThreadState::Blocked => cancel_wait(thread),
+        ThreadState::Inactive => return Ok(()),
+        _ => return Err(kerr!(ErrKind::InvalidOperation)),
+    }
+    thread.status = ThreadState::Inactive;
+    thread.notify_death();
+    Ok(())
+}
+
+/// Take blocked thread out of the queue it waits in, and fail its ipc.
+fn cancel_wait(thread: &mut ThreadControlBlock) {
+    match mem::take(&mut thread.waiting_on) {
+        WaitQueue::Endpoint(mut ep) => unsafe { ep.as_mut() }.cancel(thread),
+        WaitQueue::Notification(mut ntfn) => unsafe { ntfn.as_mut() }.cancel(thread),
+        WaitQueue::None => {}
+    }
+    thread.registers.a0 = ErrKind::IpcCancelled as usize;
+    thread.registers.a1 = 0;
+}
+
+/// Object which blocked thread waits in.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum WaitQueue {
+    #[default]
+    None,
+    Endpoint(NonNull<Endpoint>),
+    Notification(NonNull<Notification>),
 }
 
 #[derive(PartialEq, Eq, Debug, Default)]
@@ -53,23 +97,26 @@ pub struct ThreadInfo {
     pub root_cnode: CSlot<CNode>,
     pub vspace: CSlot<PageTable>,
     pub registers: Registers,
+    pub fp_registers: FpRegisters,
+    #[cfg(feature = "vector")]
+    pub vector_registers: VectorRegisters,
     pub ipc_buffer: CSlot<Page>,
+    /// Signaled when this thread stops.
+    pub death_notification: CSlot<Notification>,
+    pub waiting_on: WaitQueue,
     pub badge: usize,
-    #[cfg(debug_assertions)]
+    /// Thread id, which never changes and is unique during boot.
     pub tid: usize,
+    #[cfg(feature = "debug-syscalls")]
+    pub name: ThreadName,
 }
 
 impl ThreadInfo {
     pub fn new() -> Self {
-        let mut ret = Self::default();
-        if cfg!(debug_assertions) {
-            let tid = unsafe {
-                TCBIDX += 1;
-                TCBIDX
-            };
-            ret.tid = tid;
+        Self {
+            tid: NEXT_TID.fetch_add(1, Ordering::Relaxed),
+            ..Default::default()
         }
-        ret
     }
     pub fn resume(&mut self) {
         self.status = ThreadState::Runnable;
@@ -82,8 +129,11 @@ impl ThreadInfo {
     }
 
     pub fn set_ipc_msg(&mut self, ipc_buffer_ref: Option<&mut IPCBuffer>) {
+        // err_record is not copied, it belongs to the owner thread.
         if let (Some(reciever_ref), Some(sender_ref)) = (self.ipc_buffer_ref(), ipc_buffer_ref) {
-            unsafe { ptr::copy(sender_ref, reciever_ref, 1) }
+            reciever_ref.tag = sender_ref.tag;
+            reciever_ref.user_data = sender_ref.user_data;
+            unsafe { ptr::copy(&sender_ref.message, &mut reciever_ref.message, 1) }
         }
     }
 
@@ -105,16 +155,27 @@ impl ThreadInfo {
             root_cnode: None,
             vspace: None,
             registers: Registers::null(),
+            fp_registers: FpRegisters::null(),
+            #[cfg(feature = "vector")]
+            vector_registers: VectorRegisters::null(),
             ipc_buffer: None,
+            death_notification: None,
+            waiting_on: WaitQueue::None,
             badge: 0,
-            #[cfg(debug_assertions)]
             tid: 0,
+            #[cfg(feature = "debug-syscalls")]
+            name: ThreadName::new(),
         }
     }
 
     pub unsafe fn activate_vspace(&mut self) {
+        // idle thread runs only in kernel, so it has no vspace.
+        if self.status == ThreadState::Idle {
+            PageTable::activate_kernel_table();
+            return;
+        }
         if let Err(e) = self.activate_vspace_inner() {
-            println!("Error occured, {e:?}");
+            log_warn!("failed to activate vspace of tid {}, {e:?}", self.tid);
             PageTable::activate_kernel_table();
         }
     }
@@ -131,9 +192,11 @@ impl ThreadInfo {
     pub fn set_root_cspace(&mut self, cspace_cap: CNodeCap, parent: &mut CNodeEntry<CNode>) {
         // TODO: you should consider when already set.
         assert!(self.root_cnode.is_none(), "{:?}", self.root_cnode);
-        let mut new_entry = CNodeEntry::new_with_rawcap(cspace_cap);
+        // link after the entry is placed, or links point to the stack.
+        let new_entry = self
+            .root_cnode
+            .insert(CNodeEntry::new_with_rawcap(cspace_cap));
         new_entry.insert(parent.as_mut());
-        self.root_cnode = Some(new_entry)
     }
 
     pub fn set_root_vspace(
@@ -143,17 +206,38 @@ impl ThreadInfo {
     ) {
         // TODO: you should consider when already set.
         assert!(self.vspace.is_none(), "{:?}", self.vspace);
-        let mut new_entry = CNodeEntry::new_with_rawcap(vspace_cap);
+        let new_entry = self.vspace.insert(CNodeEntry::new_with_rawcap(vspace_cap));
         new_entry.insert(parent.as_mut());
-        self.vspace = Some(new_entry)
     }
 
     pub fn set_ipc_buffer(&mut self, page_cap: PageCap, parent: &mut CNodeEntry<Page>) {
         // TODO: check right
         // TODO: you should consider when already set.
         assert!(self.ipc_buffer.is_none());
-        let mut new_entry = CNodeEntry::new_with_rawcap(page_cap);
+        let new_entry = self
+            .ipc_buffer
+            .insert(CNodeEntry::new_with_rawcap(page_cap));
+        new_entry.insert(parent);
+    }
+
+    pub fn set_death_notification(
+        &mut self,
+        notify_cap: NotificationCap,
+        parent: &mut CNodeEntry<Notification>,
+    ) -> KernelResult<()> {
+        if self.death_notification.is_some() {
+            return Err(kerr!(ErrKind::InvalidOperation));
+        }
+        let new_entry = self
+            .death_notification
+            .insert(CNodeEntry::new_with_rawcap(notify_cap));
         new_entry.insert(parent);
-        self.ipc_buffer = Some(new_entry)
+        Ok(())
+    }
+
+    fn notify_death(&self) {
+        if let Some(entry) = self.death_notification.as_ref() {
+            entry.cap_ref().replicate().send()
+        }
     }
 }
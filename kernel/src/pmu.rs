@@ -0,0 +1,157 @@
+use crate::common::{ErrKind, KernelResult};
+use crate::kerr;
+use crate::riscv::{
+    r_counter, r_scounteren, r_scountovf, r_sie, r_sip, w_scounteren, w_sie, w_sip,
+    HPMCOUNTER_BASE, SCOUNTEREN_CY, SCOUNTEREN_IR, SCOUNTEREN_TM, SIE_LCOFIE, SIP_LCOFIP,
+};
+use crate::sbi::{
+    sbi_pmu_counter_config_matching, sbi_pmu_counter_fw_read, sbi_pmu_counter_get_info,
+    sbi_pmu_counter_start, sbi_pmu_counter_stop, SBIRet, PMU_CFG_FLAG_CLEAR_VALUE,
+    PMU_START_FLAG_SET_INIT_VALUE, PMU_STOP_FLAG_RESET,
+};
+use shared::types::CounterValue;
+
+/// Counters are indexed by counter index of SBI.
+const MAX_COUNTERS: usize = 64;
+const COUNTER_INFO_CSR_MASK: usize = 0xfff;
+const COUNTER_INFO_FIRMWARE: usize = 1 << (usize::BITS - 1);
+
+/// Counter configured through benchmark cap.
+/// Counters belong to the hart which configured them.
+#[derive(Clone, Copy)]
+struct Counter {
+    /// Counter number (csr - HPMCOUNTER_BASE), None if it is a firmware counter.
+    hw_idx: Option<usize>,
+    initial_value: usize,
+    overflows: usize,
+}
+
+// TODO: per hart
+static mut COUNTERS: [Option<Counter>; MAX_COUNTERS] = [None; MAX_COUNTERS];
+
+/// Allow user mode to read cycle, time and instret, and receive counter overflow interrupts.
+pub fn init_pmu() {
+    w_scounteren(r_scounteren() | SCOUNTEREN_CY | SCOUNTEREN_TM | SCOUNTEREN_IR);
+    // bit is hardwired to zero if Sscofpmf is not implemented.
+    w_sie(r_sie() | SIE_LCOFIE);
+}
+
+fn sbi_result(ret: SBIRet) -> KernelResult<usize> {
+    (ret.error == 0)
+        .then_some(ret.value as usize)
+        .ok_or(kerr!(ErrKind::InvalidOperation))
+}
+
+#[allow(static_mut_refs)]
+fn get_counter(counter_idx: usize) -> KernelResult<&'static mut Counter> {
+    unsafe { COUNTERS.get_mut(counter_idx) }
+        .and_then(Option::as_mut)
+        .ok_or(kerr!(ErrKind::InvalidOperation))
+}
+
+/// Find a free counter for the event (event_idx and event_data are defined by SBI PMU),
+/// and return index of it.
+/// Hardware counters can be read by user mode directly after this.
+pub fn config_counter(event_idx: usize, event_data: usize) -> KernelResult<usize> {
+    let counter_idx = sbi_result(sbi_pmu_counter_config_matching(
+        0,
+        usize::MAX,
+        PMU_CFG_FLAG_CLEAR_VALUE,
+        event_idx,
+        event_data,
+    ))?;
+    if counter_idx >= MAX_COUNTERS {
+        sbi_pmu_counter_stop(counter_idx, 1, PMU_STOP_FLAG_RESET);
+        return Err(kerr!(ErrKind::InvalidOperation));
+    }
+    let info = sbi_result(sbi_pmu_counter_get_info(counter_idx))?;
+    let hw_idx = (info & COUNTER_INFO_FIRMWARE == 0)
+        .then(|| (info & COUNTER_INFO_CSR_MASK) - HPMCOUNTER_BASE);
+    if let Some(hw_idx) = hw_idx {
+        w_scounteren(r_scounteren() | (1 << hw_idx));
+    }
+    unsafe {
+        COUNTERS[counter_idx] = Some(Counter {
+            hw_idx,
+            initial_value: 0,
+            overflows: 0,
+        })
+    };
+    Ok(counter_idx)
+}
+
+/// Counter restarts from initial_value after overflow too,
+/// so that it can be used for sampling.
+pub fn start_counter(counter_idx: usize, initial_value: usize) -> KernelResult<()> {
+    let counter = get_counter(counter_idx)?;
+    counter.initial_value = initial_value;
+    counter.overflows = 0;
+    sbi_result(sbi_pmu_counter_start(
+        counter_idx,
+        1,
+        PMU_START_FLAG_SET_INIT_VALUE,
+        initial_value,
+    ))?;
+    Ok(())
+}
+
+pub fn stop_counter(counter_idx: usize) -> KernelResult<()> {
+    get_counter(counter_idx)?;
+    sbi_result(sbi_pmu_counter_stop(counter_idx, 1, 0))?;
+    Ok(())
+}
+
+pub fn read_counter(counter_idx: usize) -> KernelResult<CounterValue> {
+    let counter = get_counter(counter_idx)?;
+    let value = match counter.hw_idx {
+        Some(hw_idx) => r_counter(hw_idx),
+        None => sbi_result(sbi_pmu_counter_fw_read(counter_idx))?,
+    };
+    Ok(CounterValue {
+        value,
+        overflows: counter.overflows,
+    })
+}
+
+/// Stop the counter and give it back to SBI.
+pub fn release_counter(counter_idx: usize) -> KernelResult<()> {
+    let counter = get_counter(counter_idx)?;
+    if let Some(hw_idx) = counter.hw_idx {
+        // cycle, time and instret are always readable.
+        if hw_idx > 2 {
+            w_scounteren(r_scounteren() & !(1 << hw_idx));
+        }
+    }
+    // counter may be stopped already, so error is ignored.
+    sbi_pmu_counter_stop(counter_idx, 1, PMU_STOP_FLAG_RESET);
+    unsafe { COUNTERS[counter_idx] = None };
+    Ok(())
+}
+
+/// Handle local counter overflow interrupt.
+/// Overflowed counters are counted and restarted from their initial value.
+#[allow(static_mut_refs)]
+pub fn handle_counter_overflow() {
+    let overflowed = r_scountovf();
+    for (counter_idx, counter) in unsafe { COUNTERS.iter_mut() }.enumerate() {
+        let Some(counter) = counter else {
+            continue;
+        };
+        let Some(hw_idx) = counter.hw_idx else {
+            continue;
+        };
+        if overflowed & (1 << hw_idx) == 0 {
+            continue;
+        }
+        counter.overflows += 1;
+        // restarting clears overflow flag of the counter.
+        sbi_pmu_counter_stop(counter_idx, 1, 0);
+        sbi_pmu_counter_start(
+            counter_idx,
+            1,
+            PMU_START_FLAG_SET_INIT_VALUE,
+            counter.initial_value,
+        );
+    }
+    w_sip(r_sip() & !SIP_LCOFIP);
+}
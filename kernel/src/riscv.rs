@@ -1,5 +1,10 @@
 #![allow(dead_code)]
 use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::object::asid_pool::hw_asid;
+use crate::sbi::sbi_remote_sfence_vma_asid;
+use crate::scheduler::get_hartid;
 
 /// supervisor-level software interrupts
 pub const SIE_SSIE: usize = 1 << 1;
@@ -7,8 +12,18 @@ pub const SIE_SSIE: usize = 1 << 1;
 pub const SIE_STIE: usize = 1 << 5;
 /// supervisor-level external interrupts
 pub const SIE_SEIE: usize = 1 << 9;
+/// local counter overflow interrupts (Sscofpmf)
+pub const SIE_LCOFIE: usize = 1 << 13;
 
 pub const SIP_STIP: usize = 1 << 5;
+pub const SIP_LCOFIP: usize = 1 << 13;
+
+/// counters which user mode can read
+pub const SCOUNTEREN_CY: usize = 1 << 0;
+pub const SCOUNTEREN_TM: usize = 1 << 1;
+pub const SCOUNTEREN_IR: usize = 1 << 2;
+/// csr number of cycle, counter n is at HPMCOUNTER_BASE + n.
+pub const HPMCOUNTER_BASE: usize = 0xc00;
 
 /// interrupt-enable bit
 pub const SSTATUS_SIE: usize = 1 << 1;
@@ -18,6 +33,18 @@ pub const SSTATUS_SPIE: usize = 1 << 5;
 pub const SSTATUS_SPP: usize = 1 << 8;
 /// permit supervisor user memory access
 pub const SSTATUS_SUM: usize = 1 << 18;
+/// vector extension state (Off, Initial, Clean, Dirty)
+pub const SSTATUS_VS: usize = 3 << 9;
+pub const SSTATUS_VS_OFF: usize = 0 << 9;
+pub const SSTATUS_VS_INITIAL: usize = 1 << 9;
+pub const SSTATUS_VS_CLEAN: usize = 2 << 9;
+pub const SSTATUS_VS_DIRTY: usize = 3 << 9;
+/// floating-point unit state (Off, Initial, Clean, Dirty)
+pub const SSTATUS_FS: usize = 3 << 13;
+pub const SSTATUS_FS_OFF: usize = 0 << 13;
+pub const SSTATUS_FS_INITIAL: usize = 1 << 13;
+pub const SSTATUS_FS_CLEAN: usize = 2 << 13;
+pub const SSTATUS_FS_DIRTY: usize = 3 << 13;
 
 macro_rules! read_csr {
     ($csr:expr) => {
@@ -99,6 +126,43 @@ pub fn w_sscratch(val: usize) {
     write_csr!("sscratch", val)
 }
 
+#[inline]
+pub fn r_scounteren() -> usize {
+    read_csr!("scounteren")
+}
+
+#[inline]
+pub fn w_scounteren(val: usize) {
+    write_csr!("scounteren", val)
+}
+
+/// bitmap of counters which overflowed, only with Sscofpmf.
+#[inline]
+pub fn r_scountovf() -> usize {
+    // csr name is not known by assembler.
+    read_csr!("0xda0")
+}
+
+macro_rules! read_counter {
+    ($n:expr, $($i:literal),+) => {
+        match $n {
+            0 => read_csr!("cycle"),
+            1 => read_csr!("time"),
+            2 => read_csr!("instret"),
+            $($i => read_csr!(concat!("hpmcounter", $i)),)+
+            _ => panic!("counter {} doesn't exist", $n),
+        }
+    };
+}
+
+/// Read counter n (0 is cycle, 1 is time, 2 is instret and others are hpmcounter n).
+pub fn r_counter(n: usize) -> usize {
+    read_counter!(
+        n, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
+        27, 28, 29, 30, 31
+    )
+}
+
 #[inline]
 pub fn wfi() {
     unsafe {
@@ -120,7 +184,62 @@ pub fn r_satp() -> usize {
     read_csr!("satp")
 }
 
+#[inline]
+pub fn w_satp(val: usize) {
+    write_csr!("satp", val)
+}
+
 #[inline]
 pub fn sfence() {
     unsafe { asm!("sfence.vma x0, x0") }
 }
+
+/// flush tlb entry of given vaddr in given address space.
+#[inline]
+pub fn sfence_vma(vaddr: usize, asid: usize) {
+    unsafe { asm!("sfence.vma {vaddr}, {asid}", vaddr = in(reg) vaddr, asid = in(reg) asid) }
+}
+
+/// flush all tlb entries of given address space, except global mapping.
+#[inline]
+pub fn sfence_vma_asid(asid: usize) {
+    unsafe { asm!("sfence.vma x0, {asid}", asid = in(reg) asid) }
+}
+
+// bitmap of harts which are running kernel.
+// only boot hart is marked until secondary harts are brought up, so remote
+// shootdown below is not exercised yet.
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn mark_hart_online(hartid: usize) {
+    ONLINE_HARTS.fetch_or(1 << hartid, Ordering::Release);
+}
+
+pub fn is_hart_online(hartid: usize) -> bool {
+    hartid < usize::BITS as usize && ONLINE_HARTS.load(Ordering::Acquire) & (1 << hartid) != 0
+}
+
+fn other_harts() -> usize {
+    ONLINE_HARTS.load(Ordering::Acquire) & !(1 << get_hartid())
+}
+
+/// flush tlb entry of a leaf mapping which starts at vaddr on every hart.
+/// one sfence.vma is enough for local hart even if the leaf is a superpage.
+pub fn flush_tlb_page(vaddr: usize, size: usize, asid: usize) {
+    let asid = hw_asid(asid);
+    sfence_vma(vaddr, asid);
+    let harts = other_harts();
+    if harts != 0 {
+        sbi_remote_sfence_vma_asid(harts, 0, vaddr, size, asid);
+    }
+}
+
+/// flush all tlb entries of given address space on every hart.
+pub fn flush_tlb_asid(asid: usize) {
+    let asid = hw_asid(asid);
+    sfence_vma_asid(asid);
+    let harts = other_harts();
+    if harts != 0 {
+        sbi_remote_sfence_vma_asid(harts, 0, 0, usize::MAX, asid);
+    }
+}
@@ -32,3 +32,149 @@ pub fn sbi_call(
 
     SBIRet { error, value }
 }
+
+#[cfg(feature = "debug-syscalls")]
+const SRST: i64 = 0x53525354;
+#[cfg(feature = "debug-syscalls")]
+const SYSTEM_RESET: i64 = 0;
+
+/// power off the whole system, it returns only if SRST extension is not available.
+#[cfg(feature = "debug-syscalls")]
+pub fn sbi_system_shutdown() -> SBIRet {
+    // reset type 0 is shutdown, reason 0 is no reason.
+    sbi_call(0, 0, 0, 0, 0, 0, SYSTEM_RESET, SRST)
+}
+
+const RFENCE: i64 = 0x52464E43;
+const REMOTE_SFENCE_VMA_ASID: i64 = 2;
+
+/// flush tlb of [start_addr, start_addr + size) in given asid on harts of hart_mask.
+/// size of usize::MAX means flushing whole address space.
+pub fn sbi_remote_sfence_vma_asid(
+    hart_mask: usize,
+    hart_mask_base: usize,
+    start_addr: usize,
+    size: usize,
+    asid: usize,
+) -> SBIRet {
+    sbi_call(
+        hart_mask as i64,
+        hart_mask_base as i64,
+        start_addr as i64,
+        size as i64,
+        asid as i64,
+        0,
+        REMOTE_SFENCE_VMA_ASID,
+        RFENCE,
+    )
+}
+
+const PMU: i64 = 0x504D55;
+const PMU_COUNTER_GET_INFO: i64 = 1;
+const PMU_COUNTER_CONFIG_MATCHING: i64 = 2;
+const PMU_COUNTER_START: i64 = 3;
+const PMU_COUNTER_STOP: i64 = 4;
+const PMU_COUNTER_FW_READ: i64 = 5;
+/// counter value is cleared when counter is configured.
+pub const PMU_CFG_FLAG_CLEAR_VALUE: usize = 1 << 1;
+/// counter starts from given initial value.
+pub const PMU_START_FLAG_SET_INIT_VALUE: usize = 1 << 0;
+/// counter is released after stopped.
+pub const PMU_STOP_FLAG_RESET: usize = 1 << 0;
+
+/// value is | type 1 | reserved | width 6 | csr 12 |, type 1 means firmware counter.
+pub fn sbi_pmu_counter_get_info(counter_idx: usize) -> SBIRet {
+    sbi_call(counter_idx as i64, 0, 0, 0, 0, 0, PMU_COUNTER_GET_INFO, PMU)
+}
+
+/// find a counter in counter_idx_base + bits of counter_idx_mask which can monitor the event,
+/// and value is index of the counter.
+pub fn sbi_pmu_counter_config_matching(
+    counter_idx_base: usize,
+    counter_idx_mask: usize,
+    config_flags: usize,
+    event_idx: usize,
+    event_data: usize,
+) -> SBIRet {
+    sbi_call(
+        counter_idx_base as i64,
+        counter_idx_mask as i64,
+        config_flags as i64,
+        event_idx as i64,
+        event_data as i64,
+        0,
+        PMU_COUNTER_CONFIG_MATCHING,
+        PMU,
+    )
+}
+
+pub fn sbi_pmu_counter_start(
+    counter_idx_base: usize,
+    counter_idx_mask: usize,
+    start_flags: usize,
+    initial_value: usize,
+) -> SBIRet {
+    sbi_call(
+        counter_idx_base as i64,
+        counter_idx_mask as i64,
+        start_flags as i64,
+        initial_value as i64,
+        0,
+        0,
+        PMU_COUNTER_START,
+        PMU,
+    )
+}
+
+pub fn sbi_pmu_counter_stop(
+    counter_idx_base: usize,
+    counter_idx_mask: usize,
+    stop_flags: usize,
+) -> SBIRet {
+    sbi_call(
+        counter_idx_base as i64,
+        counter_idx_mask as i64,
+        stop_flags as i64,
+        0,
+        0,
+        0,
+        PMU_COUNTER_STOP,
+        PMU,
+    )
+}
+
+/// firmware counters can't be read through csr.
+pub fn sbi_pmu_counter_fw_read(counter_idx: usize) -> SBIRet {
+    sbi_call(counter_idx as i64, 0, 0, 0, 0, 0, PMU_COUNTER_FW_READ, PMU)
+}
+
+/// SBI call is not supported, for example the extension is not implemented.
+pub const SBI_ERR_NOT_SUPPORTED: u64 = -2i64 as u64;
+
+const LEGACY_CONSOLE_PUTCHAR: i64 = 1;
+
+/// legacy extension which writes a byte synchronously.
+pub fn sbi_console_putchar(ch: u8) -> SBIRet {
+    sbi_call(ch as i64, 0, 0, 0, 0, 0, 0, LEGACY_CONSOLE_PUTCHAR)
+}
+
+const DBCN: i64 = 0x4442434E;
+const CONSOLE_WRITE: i64 = 0;
+
+/// write bytes at physical address to debug console, value is number of bytes written.
+pub fn sbi_debug_console_write(
+    num_bytes: usize,
+    base_addr_lo: usize,
+    base_addr_hi: usize,
+) -> SBIRet {
+    sbi_call(
+        num_bytes as i64,
+        base_addr_lo as i64,
+        base_addr_hi as i64,
+        0,
+        0,
+        0,
+        CONSOLE_WRITE,
+        DBCN,
+    )
+}
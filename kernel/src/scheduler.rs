@@ -1,11 +1,23 @@
+use crate::common::{ErrKind, KernelResult};
+use crate::kerr;
 use crate::list::{LinkedList, ListItem};
-use crate::object::{Registers, ThreadControlBlock, ThreadInfo};
-use crate::println;
-use crate::riscv::{r_sstatus, w_sstatus, wfi, SSTATUS_SIE, SSTATUS_SPIE, SSTATUS_SPP};
+use crate::object::{Registers, ThreadControlBlock, ThreadInfo, ThreadState};
+use crate::riscv::{
+    is_hart_online, r_sstatus, r_time, w_sstatus, wfi, SSTATUS_SIE, SSTATUS_SPIE, SSTATUS_SPP,
+};
+use crate::stack::{idle_stack_top, MAX_HARTS};
+#[cfg(feature = "trace")]
+use crate::trace::trace_context_switch;
+use crate::uart::drain_console;
 use core::ptr;
+use shared::types::IdleTime;
+
+// TODO: use once_cell
+static mut IDLE_THREADS: [ThreadControlBlock; MAX_HARTS] =
+    [const { ThreadControlBlock::new(ThreadInfo::idle_init()) }; MAX_HARTS];
 
 // TODO: use once_cell
-pub static mut IDLE_THREAD: ThreadControlBlock = ThreadControlBlock::new(ThreadInfo::idle_init());
+static mut IDLE_STATS: [IdleStat; MAX_HARTS] = [const { IdleStat::new() }; MAX_HARTS];
 
 // TODO: use unsafe_cell
 pub static mut CURRENT_PROC: *mut ThreadControlBlock = ptr::null_mut();
@@ -16,6 +28,8 @@ pub static mut CPU_VAR: CpuVar = CpuVar {
     sptop: 0,
     sscratch: 0,
     cur_reg_base: ptr::null_mut(),
+    hartid: 0,
+    fpu_owner: ptr::null_mut(),
 };
 
 // TODO: use unsafe_cell
@@ -27,6 +41,39 @@ pub struct CpuVar {
     pub sptop: usize,
     pub sscratch: usize,
     pub cur_reg_base: *mut Registers,
+    pub hartid: usize,
+    /// Thread whose state is in registers of fpu.
+    pub fpu_owner: *mut ThreadControlBlock,
+}
+
+/// Time which a hart spent in its idle thread.
+struct IdleStat {
+    /// Time when the hart entered idle thread, None if it is running other thread.
+    idle_since: Option<usize>,
+    total: usize,
+}
+
+impl IdleStat {
+    const fn new() -> Self {
+        Self {
+            idle_since: None,
+            total: 0,
+        }
+    }
+
+    fn enter(&mut self, now: usize) {
+        self.idle_since = Some(now);
+    }
+
+    fn leave(&mut self, now: usize) {
+        if let Some(since) = self.idle_since.take() {
+            self.total += now - since;
+        }
+    }
+
+    fn idle_time(&self, now: usize) -> usize {
+        self.total + self.idle_since.map_or(0, |since| now - since)
+    }
 }
 
 #[derive(Default)]
@@ -69,8 +116,20 @@ pub unsafe fn schedule() {
             (*CURRENT_PROC).set_timeout(TASK_QUANTUM);
             return;
         }
-        &raw mut IDLE_THREAD
+        idle_thread(CPU_VAR.hartid)
     };
+    let now = r_time();
+    let idle_stat = &mut IDLE_STATS[CPU_VAR.hartid];
+    if is_idle(CURRENT_PROC) {
+        idle_stat.leave(now);
+    }
+    if is_idle(next) {
+        idle_stat.enter(now);
+    }
+    #[cfg(feature = "trace")]
+    if next != CURRENT_PROC {
+        trace_context_switch((*CURRENT_PROC).tid, (*next).tid);
+    }
     // change page table
     (*next).activate_vspace();
     unsafe {
@@ -80,21 +139,46 @@ pub unsafe fn schedule() {
     SCHEDULER.requested = false;
 }
 
-pub fn create_idle_thread(stack_top: usize) {
+fn idle_thread(hartid: usize) -> *mut ThreadControlBlock {
+    unsafe { &raw mut IDLE_THREADS[hartid] }
+}
+
+unsafe fn is_idle(tcb: *const ThreadControlBlock) -> bool {
+    (*tcb).status == ThreadState::Idle
+}
+
+/// Idle thread runs in supervisor mode with its own stack, and it is used until the first schedule.
+pub fn create_idle_thread(hartid: usize) {
     unsafe {
-        IDLE_THREAD.registers.sepc = idle as usize;
-        IDLE_THREAD.registers.sstatus = SSTATUS_SPP | SSTATUS_SPIE;
-        IDLE_THREAD.registers.sp = stack_top;
-        CURRENT_PROC = &raw mut IDLE_THREAD;
-        CPU_VAR.cur_reg_base = &raw mut IDLE_THREAD.registers;
-        CPU_VAR.sptop = stack_top;
+        let idle_tcb = idle_thread(hartid);
+        (*idle_tcb).registers.sepc = idle as usize;
+        (*idle_tcb).registers.sstatus = SSTATUS_SPP | SSTATUS_SPIE;
+        (*idle_tcb).registers.sp = idle_stack_top(hartid);
+        CURRENT_PROC = idle_tcb;
+        CPU_VAR.cur_reg_base = &raw mut (*idle_tcb).registers;
+        IDLE_STATS[hartid].enter(r_time());
     }
 }
 
+/// Return how long the hart has been idle since boot.
+#[allow(static_mut_refs)]
+pub fn get_idle_time(hartid: usize) -> KernelResult<IdleTime> {
+    (hartid < MAX_HARTS && is_hart_online(hartid))
+        .then_some(())
+        .ok_or(kerr!(ErrKind::InvalidOperation))?;
+    let now = r_time();
+    Ok(IdleTime {
+        idle_time: unsafe { IDLE_STATS[hartid].idle_time(now) },
+        up_time: now,
+    })
+}
+
 #[no_mangle]
 fn idle() -> ! {
-    println!("In the Idle");
     loop {
+        // console lock must not be held when timer interrupt comes.
+        w_sstatus(r_sstatus() & !SSTATUS_SIE);
+        drain_console();
         w_sstatus(r_sstatus() | SSTATUS_SIE);
         wfi();
     }
@@ -106,6 +190,12 @@ pub fn push(tcb: &mut ThreadControlBlock) {
     unsafe { SCHEDULER.push(tcb) }
 }
 
+// TODO: remove this attribute
+#[allow(static_mut_refs)]
+pub fn remove(tcb: &mut ThreadControlBlock) -> bool {
+    unsafe { SCHEDULER.runqueue.remove(tcb) }
+}
+
 pub fn get_current_tcb_mut<'a>() -> &'a mut ThreadControlBlock {
     unsafe { &mut *CURRENT_PROC }
 }
@@ -117,16 +207,44 @@ pub fn require_schedule() {
 
 pub fn timer_tick() {
     unsafe {
-        if CURRENT_PROC == &raw mut IDLE_THREAD {
+        // ticks during a long syscall are not counted after time slice runs out.
+        if is_idle(CURRENT_PROC) || SCHEDULER.requested {
             return;
         }
-        (*CURRENT_PROC).time_slice -= 1;
+        (*CURRENT_PROC).time_slice = (*CURRENT_PROC).time_slice.saturating_sub(1);
         if (*CURRENT_PROC).time_slice == 0 {
             require_schedule()
         }
     }
 }
 
+/// Print current thread, runqueue and idle time of this hart.
+#[cfg(feature = "debug-syscalls")]
+#[allow(static_mut_refs)]
+pub fn dump_scheduler() {
+    use crate::println;
+    unsafe {
+        let hartid = CPU_VAR.hartid;
+        let current = &*CURRENT_PROC;
+        println!("scheduler of hart {}", hartid);
+        println!(
+            "  current: tid {} {:?} {:?}",
+            current.tid, current.name, current.status
+        );
+        for tcb in SCHEDULER.runqueue.iter() {
+            println!(
+                "  runqueue: tid {} {:?} time slice {}",
+                tcb.tid, tcb.name, tcb.time_slice
+            );
+        }
+        println!("  idle time: {}", IDLE_STATS[hartid].idle_time(r_time()));
+    }
+}
+
 pub fn get_current_reg<'a>() -> &'a mut Registers {
     unsafe { &mut *(CPU_VAR.cur_reg_base) }
 }
+
+pub fn get_hartid() -> usize {
+    unsafe { CPU_VAR.hartid }
+}
@@ -0,0 +1,118 @@
+use crate::address::PAGE_SIZE;
+
+use core::fmt;
+#[cfg(debug_assertions)]
+use core::ptr;
+
+extern "C" {
+    static __stack_guard: u8;
+}
+
+pub const MAX_HARTS: usize = 8;
+pub const KERNEL_STACK_SIZE: usize = 16 * PAGE_SIZE;
+/// Idle thread only waits for interrupts, so one page is enough.
+pub const IDLE_STACK_SIZE: usize = PAGE_SIZE;
+/// Written at the bottom of each kernel stack and checked on trap exit in debug build.
+#[cfg(debug_assertions)]
+const STACK_CANARY: usize = 0xdead_beef_cafe_babe;
+
+/// Kernel stack of one hart, used while handling traps.
+/// Guard page is at lower address and it is unmapped at kernel vm initialization,
+/// so that stack overflow causes page fault instead of corrupting next stack.
+#[repr(C, align(4096))]
+pub struct KernelStack {
+    guard: [u8; PAGE_SIZE],
+    stack: [u8; KERNEL_STACK_SIZE],
+}
+
+impl KernelStack {
+    const fn new() -> Self {
+        Self {
+            guard: [0; PAGE_SIZE],
+            stack: [0; KERNEL_STACK_SIZE],
+        }
+    }
+}
+
+static mut KERNEL_STACKS: [KernelStack; MAX_HARTS] = [const { KernelStack::new() }; MAX_HARTS];
+
+/// Stack of idle thread of one hart.
+/// Idle thread is interrupted at any point, so it can't share stack with trap handler.
+#[repr(C, align(4096))]
+struct IdleStack([u8; IDLE_STACK_SIZE]);
+
+static mut IDLE_STACKS: [IdleStack; MAX_HARTS] =
+    [const { IdleStack([0; IDLE_STACK_SIZE]) }; MAX_HARTS];
+
+fn kernel_stack(hartid: usize) -> *mut KernelStack {
+    assert!(hartid < MAX_HARTS, "hart {} exceeds MAX_HARTS", hartid);
+    unsafe { &raw mut KERNEL_STACKS[hartid] }
+}
+
+fn stack_bottom(hartid: usize) -> usize {
+    unsafe { &raw mut (*kernel_stack(hartid)).stack as usize }
+}
+
+pub fn stack_top(hartid: usize) -> usize {
+    stack_bottom(hartid) + KERNEL_STACK_SIZE
+}
+
+pub fn idle_stack_top(hartid: usize) -> usize {
+    assert!(hartid < MAX_HARTS, "hart {} exceeds MAX_HARTS", hartid);
+    unsafe { &raw mut IDLE_STACKS[hartid] as usize + IDLE_STACK_SIZE }
+}
+
+pub fn guard_page(hartid: usize) -> usize {
+    unsafe { &raw mut (*kernel_stack(hartid)).guard as usize }
+}
+
+/// Stack which overflowed into its guard page.
+pub enum OverflowedStack {
+    /// Stack given by kernel.ld, used at boot.
+    Boot,
+    /// Trap handling stack of the hart.
+    Hart(usize),
+}
+
+impl fmt::Display for OverflowedStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boot => write!(f, "boot stack"),
+            Self::Hart(hartid) => write!(f, "kernel stack of hart {}", hartid),
+        }
+    }
+}
+
+/// Find stack whose guard page contains addr.
+pub fn find_overflowed_stack(addr: usize) -> Option<OverflowedStack> {
+    let boot_guard = &raw const __stack_guard as usize;
+    if (boot_guard..boot_guard + PAGE_SIZE).contains(&addr) {
+        return Some(OverflowedStack::Boot);
+    }
+    (0..MAX_HARTS)
+        .find(|&hartid| {
+            let guard = guard_page(hartid);
+            (guard..guard + PAGE_SIZE).contains(&addr)
+        })
+        .map(OverflowedStack::Hart)
+}
+
+#[cfg(debug_assertions)]
+pub fn init_canary(hartid: usize) {
+    unsafe { ptr::write_volatile(stack_bottom(hartid) as *mut usize, STACK_CANARY) }
+}
+
+/// Panic if canary of the stack was overwritten.
+/// It detects writes which reached the bottom of the stack but didn't touch guard page.
+#[cfg(debug_assertions)]
+pub fn check_canary(hartid: usize) {
+    let canary = unsafe { ptr::read_volatile(stack_bottom(hartid) as *const usize) };
+    if canary != STACK_CANARY {
+        panic!(
+            "kernel stack overflow: canary of hart {} stack [{:#x}, {:#x}) was overwritten",
+            hartid,
+            stack_bottom(hartid),
+            stack_top(hartid)
+        );
+    }
+}
@@ -1,26 +1,37 @@
+#[cfg(feature = "debug-syscalls")]
+use crate::debug::handle_debug_syscall;
+#[cfg(feature = "trace")]
+use crate::trace::{trace_cap_type, trace_syscall};
+#[cfg(feature = "debug-syscalls")]
+use crate::uart::putchar;
 use crate::{
-    address::PAGE_SIZE,
-    capability::{cap_try_from_u8, CapabilityType},
-    common::{is_aligned, ErrKind, KernelResult},
-    kerr,
+    address::{PAGE_SIZE, USER_VADDR_LIMIT},
+    capability::{cap_try_from_u8, cnode::CNodeCap, Capability, CapabilityData, CapabilityType},
+    common::{is_aligned, ArgContext, ErrKind, KernelResult},
+    kerr, log_debug,
     object::{
-        get_user_flags,
+        get_user_flags, move_entries,
         page_table::{Page, PAGE_U},
-        CNode, CNodeEntry, Endpoint, Notification, PageTable, Registers, ThreadControlBlock,
-        Untyped,
+        suspend, AsidControl, AsidPool, Benchmark, CNode, CNodeEntry, CSlot, Endpoint, KObject,
+        Notification, PageTable, Registers, SchedControl, ThreadControlBlock, Untyped,
     },
-    println,
     scheduler::{get_current_tcb_mut, require_schedule},
-    uart::putchar,
 };
+use core::ptr;
 pub use shared::inv_labels::InvLabel;
 pub use shared::syscall_no::SysCallNo;
+use shared::types::{COPY_REGS_FP, COPY_REGS_RESUME_TARGET, COPY_REGS_SUSPEND_SOURCE};
 
 pub fn handle_syscall(syscall_n: usize, reg: &mut Registers) {
     let cap_ptr = reg.a0;
     let depth = reg.a1;
-    let syscall_ret = if let Ok(inv_label) = InvLabel::try_from(reg.a2) {
+    #[cfg(feature = "trace")]
+    let label = reg.a2;
+    let syscall_ret = if syscall_n == SysCallNo::Exit as usize {
+        suspend(get_current_tcb_mut()).map(|_| None)
+    } else if let Ok(inv_label) = InvLabel::try_from(reg.a2) {
         match syscall_n {
+            #[cfg(feature = "debug-syscalls")]
             n if n == SysCallNo::Print as usize => match inv_label {
                 InvLabel::PutChar => {
                     let a0 = reg.a0;
@@ -34,20 +45,39 @@ pub fn handle_syscall(syscall_n: usize, reg: &mut Registers) {
                 }
                 _ => Err(kerr!(ErrKind::UnknownSysCall)),
             },
+            #[cfg(not(feature = "debug-syscalls"))]
+            n if n == SysCallNo::Print as usize => Err(kerr!(ErrKind::UnknownSysCall)),
+            #[cfg(feature = "debug-syscalls")]
+            n if n == SysCallNo::Debug as usize => handle_debug_syscall(inv_label, reg),
+            #[cfg(not(feature = "debug-syscalls"))]
+            n if n == SysCallNo::Debug as usize => Err(kerr!(ErrKind::UnknownSysCall)),
             _ => {
                 // Why don't you use "?"?
                 handle_invocation(cap_ptr, depth, inv_label, syscall_n, reg)
             }
         }
     } else {
-        println!("{}, {}", syscall_n, syscall_n);
+        log_debug!(
+            "unknown invocation label {} of syscall {}",
+            reg.a2,
+            syscall_n
+        );
         Err(kerr!(ErrKind::UnknownInvocation))
     };
+    #[cfg(feature = "trace")]
+    trace_syscall(
+        syscall_n,
+        label,
+        syscall_ret.as_ref().err().map(|e| e.e_kind),
+    );
     match syscall_ret {
         Err(e) => {
-            println!("system call failed, {:?}", e);
+            log_debug!("system call failed, {:?}", e);
             reg.a0 = e.e_kind as usize;
             reg.a1 = e.e_val as usize;
+            if let Some(ipc_buffer) = get_current_tcb_mut().ipc_buffer_ref() {
+                ipc_buffer.err_record = e.to_record();
+            }
         }
         Ok(ret) => {
             reg.a0 = 0;
@@ -67,7 +97,7 @@ fn handle_invocation(
     inv_label: InvLabel,
     // TODO: Call or Send or Recv or NonBlocking Send or ..
     _syscall_n: usize,
-    reg: &Registers,
+    reg: &mut Registers,
 ) -> KernelResult<Option<usize>> {
     let current_tcb = get_current_tcb_mut();
     let ipc_buffer = current_tcb.ipc_buffer_ref();
@@ -81,10 +111,13 @@ fn handle_invocation(
     let mut root_cnode_2 = root_cnode.replicate();
 
     let slot = root_cnode_2
-        .lookup_entry_mut(cap_ptr, depth as u32)?
+        .lookup_entry_mut(cap_ptr, depth as u32)
+        .for_arg(0)?
         .as_mut()
-        .ok_or(kerr!(ErrKind::SlotIsEmpty))?;
+        .ok_or(kerr!(ErrKind::SlotIsEmpty).with_arg(0))?;
     let cap_type = slot.get_cap_type()?;
+    #[cfg(feature = "trace")]
+    trace_cap_type(cap_type);
     // TODO: Into Capability::invoke()
     match cap_type {
         CapabilityType::Untyped => {
@@ -95,54 +128,102 @@ fn handle_invocation(
             let index = index_and_depth >> 32;
             let user_size = user_size_and_num >> 32;
             let num = user_size_and_num as u32;
-            let new_type = cap_try_from_u8(reg.a6 as u8)?;
-            let dest_cnode_cap = root_cnode
-                .lookup_entry_mut(dest_cnode_ptr, dest_depth)?
-                .as_mut()
-                .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                .as_capability::<CNode>()?
-                .cap_ref_mut();
-            let dest_cnode = dest_cnode_cap.get_writable(num, index as u32)?;
+            let new_type = cap_try_from_u8(reg.a6 as u8).for_arg(6)?;
+            let dest_cnode_cap =
+                lookup_cap::<CNode>(&mut root_cnode, dest_cnode_ptr, dest_depth, 3)?.cap_ref_mut();
+            let dest_cnode = dest_cnode_cap.get_writable(num, index as u32).for_arg(4)?;
             let (src_cap, src_mdb) = slot.cap_and_mdb_ref_mut();
-            src_cap.try_ref_mut_as::<Untyped>()?.invoke_retype(
+            let result = src_cap.try_ref_mut_as::<Untyped>()?.invoke_retype(
                 src_mdb,
                 dest_cnode,
                 user_size,
                 num as usize,
                 new_type,
             )?;
-            // TODO: return user how match bit was used
-            Ok(None)
+            // free offset is the second return value, so ipc buffer is not touched.
+            reg.a2 = result.free_offset;
+            Ok(Some(result.num))
         }
         CapabilityType::CNode => {
             let src_index = reg.a3;
+            // a4 is | src_depth 32 | pivot_depth 16 | dest_depth 16 |, pivot is only for rotate.
             let src_depth = (reg.a4 >> 32) as u32;
-            let dest_depth = reg.a4 as u32;
+            let pivot_depth = ((reg.a4 >> 16) & 0xffff) as u32;
+            let dest_depth = (reg.a4 & 0xffff) as u32;
+            if [src_depth, pivot_depth, dest_depth]
+                .iter()
+                .any(|depth| *depth > usize::BITS)
+                || (inv_label != InvLabel::CNodeRotate && pivot_depth != 0)
+            {
+                return Err(kerr!(ErrKind::InvalidOperation).with_arg(4));
+            }
             let dest_index = reg.a5;
 
             let dest_root = slot.cap_ref_mut().try_ref_mut_as::<CNode>()?;
-            let src_slot = root_cnode.lookup_entry_mut(src_index, src_depth)?;
-            let src_entry = src_slot.as_mut().ok_or(kerr!(ErrKind::SlotIsEmpty))?;
-            let dest_slot = dest_root.lookup_entry_mut(dest_index, dest_depth)?;
-            if dest_slot.is_some() {
-                Err(kerr!(ErrKind::NotEmptySlot))
-            } else {
-                // TODO: Whether this cap is derivable
-                let raw_cap = src_entry.derive()?;
-                let mut cap = raw_cap;
-                if inv_label == InvLabel::CNodeMint {
-                    let cap_val = reg.a6;
-                    cap.set_cap_dep_val(cap_val);
-                }
-                let mut new_slot = CNodeEntry::new_with_rawcap(cap);
-                if inv_label == InvLabel::CNodeMove {
-                    new_slot.replace(src_entry);
-                    *src_slot = None
-                } else {
-                    new_slot.insert(src_entry);
-                }
-                *dest_slot = Some(new_slot);
-                Ok(None)
+            let src_slot = root_cnode
+                .lookup_entry_mut(src_index, src_depth)
+                .for_arg(3)?;
+            if src_slot.is_none() {
+                return Err(kerr!(ErrKind::SlotIsEmpty).with_arg(3));
+            }
+            let dest_slot = dest_root
+                .lookup_entry_mut(dest_index, dest_depth)
+                .for_arg(5)?;
+            // slots can be the same one, so raw pointers are used from here.
+            let src_slot = src_slot as *mut CSlot;
+            let dest_slot = dest_slot as *mut CSlot;
+            match inv_label {
+                InvLabel::CNodeSwap => {
+                    if unsafe { (*dest_slot).is_none() } {
+                        return Err(kerr!(ErrKind::SlotIsEmpty).with_arg(5));
+                    }
+                    if !ptr::eq(src_slot, dest_slot) {
+                        unsafe { move_entries([(src_slot, dest_slot), (dest_slot, src_slot)]) }
+                    }
+                    Ok(None)
+                }
+                InvLabel::CNodeRotate => {
+                    let pivot_slot = root_cnode
+                        .lookup_entry_mut(reg.a6, pivot_depth)
+                        .for_arg(6)? as *mut CSlot;
+                    if unsafe { (*pivot_slot).is_none() } {
+                        return Err(kerr!(ErrKind::SlotIsEmpty).with_arg(6));
+                    }
+                    if ptr::eq(pivot_slot, src_slot) || ptr::eq(pivot_slot, dest_slot) {
+                        return Err(kerr!(ErrKind::InvalidOperation).with_arg(6));
+                    }
+                    // pivot goes to dest, then src goes to pivot.
+                    if ptr::eq(src_slot, dest_slot) {
+                        unsafe { move_entries([(pivot_slot, src_slot), (src_slot, pivot_slot)]) }
+                    } else if unsafe { (*dest_slot).is_some() } {
+                        return Err(kerr!(ErrKind::NotEmptySlot).with_arg(5));
+                    } else {
+                        unsafe { move_entries([(pivot_slot, dest_slot), (src_slot, pivot_slot)]) }
+                    }
+                    Ok(None)
+                }
+                _ => {
+                    let (src_slot, dest_slot) = unsafe { (&mut *src_slot, &mut *dest_slot) };
+                    let src_entry = src_slot.as_mut().unwrap();
+                    if dest_slot.is_some() {
+                        return Err(kerr!(ErrKind::NotEmptySlot).with_arg(5));
+                    }
+                    // TODO: Whether this cap is derivable
+                    let cap = if inv_label == InvLabel::CNodeMint {
+                        src_entry.mint(reg.a6)?
+                    } else {
+                        src_entry.derive()?
+                    };
+                    // link after the entry is placed, or links point to the stack.
+                    let new_entry = dest_slot.insert(CNodeEntry::new_with_rawcap(cap));
+                    if inv_label == InvLabel::CNodeMove {
+                        new_entry.replace(src_entry);
+                        *src_slot = None
+                    } else {
+                        new_entry.insert(src_entry);
+                    }
+                    Ok(None)
+                }
             }
         }
         CapabilityType::Tcb => {
@@ -161,12 +242,14 @@ fn handle_invocation(
                     )?;
                     let cspace_slot = cnode_slot
                         .as_mut()
-                        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                        .as_capability::<CNode>()?;
+                        .ok_or(kerr!(ErrKind::SlotIsEmpty))
+                        .and_then(|entry| entry.as_capability::<CNode>())
+                        .for_arg(3)?;
                     let vspace_slot = vspace_slot
                         .as_mut()
-                        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                        .as_capability::<PageTable>()?;
+                        .ok_or(kerr!(ErrKind::SlotIsEmpty))
+                        .and_then(|entry| entry.as_capability::<PageTable>())
+                        .for_arg(5)?;
                     tcb_cap.set_cspace(cspace_slot)?;
                     tcb_cap.set_vspace(vspace_slot)?;
                     Ok(None)
@@ -176,14 +259,60 @@ fn handle_invocation(
                     tcb_cap.set_registers(registers);
                     Ok(None)
                 }
+                InvLabel::TcbReadRegisters => {
+                    let registers = tcb_cap.get_registers()?;
+                    ipc_buffer
+                        .ok_or(kerr!(ErrKind::InvalidOperation))?
+                        .write_as(|| registers)
+                        .map_err(|e_kind| kerr!(e_kind))?;
+                    Ok(None)
+                }
+                InvLabel::TcbCopyRegisters => {
+                    let src_ptr = reg.a3;
+                    let src_depth = reg.a4 as u32;
+                    let flags = reg.a5;
+                    let src_cap =
+                        lookup_cap::<ThreadControlBlock>(&mut root_cnode, src_ptr, src_depth, 3)?
+                            .cap_ref_mut();
+                    if flags & COPY_REGS_SUSPEND_SOURCE != 0 {
+                        src_cap.suspend()?;
+                    }
+                    tcb_cap.copy_registers(src_cap, flags & COPY_REGS_FP != 0);
+                    // if source is caller, target starts after ecall as if this invocation succeeded,
+                    // and a1 tells which is which like fork.
+                    let is_caller = ptr::eq(src_cap.get_tcb(), get_current_tcb_mut());
+                    if is_caller {
+                        let registers = &mut tcb_cap.get_tcb().registers;
+                        registers.a0 = 0;
+                        registers.a1 = 0;
+                        registers.sepc += 4;
+                    }
+                    if flags & COPY_REGS_RESUME_TARGET != 0 {
+                        tcb_cap.make_runnable();
+                    }
+                    Ok(is_caller.then_some(1))
+                }
+                InvLabel::TcbSetTLSBase => {
+                    tcb_cap.set_tls_base(reg.a3);
+                    Ok(None)
+                }
+                InvLabel::TcbGetId => Ok(Some(tcb_cap.get_id())),
+                InvLabel::TcbSuspend => {
+                    tcb_cap.suspend()?;
+                    Ok(None)
+                }
+                InvLabel::TcbBindDeathNotification => {
+                    let notify_ptr = reg.a3;
+                    let notify_depth = reg.a4 as u32;
+                    let notify_entry =
+                        lookup_cap::<Notification>(&mut root_cnode, notify_ptr, notify_depth, 3)?;
+                    tcb_cap.bind_death_notification(notify_entry)?;
+                    Ok(None)
+                }
                 InvLabel::TcbSetIpcBuffer => {
                     let page_ptr = reg.a3;
                     let page_deph = reg.a4 as u32;
-                    let page_cap = root_cnode
-                        .lookup_entry_mut(page_ptr, page_deph)?
-                        .as_mut()
-                        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                        .as_capability::<Page>()?;
+                    let page_cap = lookup_cap::<Page>(&mut root_cnode, page_ptr, page_deph, 3)?;
                     tcb_cap.set_ipc_buffer(page_cap)?;
                     Ok(None)
                 }
@@ -240,29 +369,38 @@ fn handle_invocation(
                     let page_table_ptr = reg.a3;
                     let page_table_depth = reg.a4 as u32;
                     let vaddr = reg.a5;
-                    is_aligned(vaddr, PAGE_SIZE)
+                    let page_size = page_cap.get_page_size();
+                    is_aligned(vaddr, page_size.bytes())
                         .then_some(())
-                        .ok_or(kerr!(ErrKind::NotAligned, PAGE_SIZE as u16))?;
-                    let root_page_table = root_cnode
-                        .lookup_entry_mut(page_table_ptr, page_table_depth)?
-                        .as_mut()
-                        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                        .cap_ref_mut()
-                        .try_ref_mut_as::<PageTable>()?;
-                    let flags = PAGE_U | get_user_flags(reg.a6);
+                        .ok_or(kerr!(ErrKind::NotAligned, page_size.bits() as u16).with_arg(5))?;
+                    // upper half is for kernel, and its width depends on paging mode.
+                    (vaddr < USER_VADDR_LIMIT)
+                        .then_some(())
+                        .ok_or(kerr!(ErrKind::InvalidUserAddress).with_arg(5))?;
+                    let root_page_table = lookup_cap::<PageTable>(
+                        &mut root_cnode,
+                        page_table_ptr,
+                        page_table_depth,
+                        3,
+                    )?
+                    .cap_ref_mut();
+                    // flags are limited by rights of the cap, and checked after that
+                    // because limit can leave no valid permission.
+                    let flags = PAGE_U | get_user_flags(page_cap.limit_flags(reg.a6));
                     page_cap.map(root_page_table, vaddr.into(), flags)?;
                     Ok(None)
                 }
+                InvLabel::PageGetAddress => {
+                    let address = page_cap.get_physical_address()?;
+                    Ok(Some(address.into()))
+                }
+                InvLabel::PageRemap => {
+                    let flags = PAGE_U | get_user_flags(page_cap.limit_flags(reg.a3));
+                    page_cap.remap(flags)?;
+                    Ok(None)
+                }
                 InvLabel::PageUnMap => {
-                    let page_table_ptr = reg.a3;
-                    let page_table_depth = reg.a4 as u32;
-                    let root_page_table = root_cnode
-                        .lookup_entry_mut(page_table_ptr, page_table_depth)?
-                        .as_mut()
-                        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                        .cap_ref_mut()
-                        .try_ref_mut_as::<PageTable>()?;
-                    page_cap.unmap(root_page_table)?;
+                    page_cap.unmap()?;
                     Ok(None)
                 }
                 _ => Err(kerr!(ErrKind::UnknownInvocation)),
@@ -277,13 +415,17 @@ fn handle_invocation(
                     let vaddr = reg.a5;
                     is_aligned(vaddr, PAGE_SIZE)
                         .then_some(())
-                        .ok_or(kerr!(ErrKind::NotAligned, PAGE_SIZE as u16))?;
-                    let root_page_table = root_cnode
-                        .lookup_entry_mut(page_table_ptr, page_table_depth)?
-                        .as_mut()
-                        .ok_or(kerr!(ErrKind::SlotIsEmpty))?
-                        .cap_ref_mut()
-                        .try_ref_mut_as::<PageTable>()?;
+                        .ok_or(kerr!(ErrKind::NotAligned, PAGE_SIZE as u16).with_arg(5))?;
+                    (vaddr < USER_VADDR_LIMIT)
+                        .then_some(())
+                        .ok_or(kerr!(ErrKind::InvalidUserAddress).with_arg(5))?;
+                    let root_page_table = lookup_cap::<PageTable>(
+                        &mut root_cnode,
+                        page_table_ptr,
+                        page_table_depth,
+                        3,
+                    )?
+                    .cap_ref_mut();
                     let v = page_table_cap.map(root_page_table, vaddr.into())?;
                     Ok(Some(v))
                 }
@@ -291,7 +433,92 @@ fn handle_invocation(
                     todo!()
                 }
                 InvLabel::PageTableMakeRoot => {
-                    page_table_cap.make_as_root()?;
+                    let asid_pool_ptr = reg.a3;
+                    let asid_pool_depth = reg.a4 as u32;
+                    let asid_pool_cap =
+                        lookup_cap::<AsidPool>(&mut root_cnode, asid_pool_ptr, asid_pool_depth, 3)?
+                            .cap_ref_mut();
+                    page_table_cap.make_as_root(asid_pool_cap)?;
+                    Ok(None)
+                }
+                _ => Err(kerr!(ErrKind::UnknownInvocation)),
+            }
+        }
+        CapabilityType::AsidControl => {
+            let asid_control_cap = slot.cap_ref_mut().try_ref_mut_as::<AsidControl>()?;
+            match inv_label {
+                InvLabel::AsidControlMakePool => {
+                    let untyped_ptr = reg.a3;
+                    let untyped_depth = reg.a4 as u32;
+                    let dest_ptr = reg.a5;
+                    let dest_depth = reg.a6 as u32;
+                    let (untyped_slot, dest_slot) = root_cnode.lookup_two_entries_mut(
+                        untyped_ptr,
+                        untyped_depth,
+                        dest_ptr,
+                        dest_depth,
+                    )?;
+                    let untyped_entry = untyped_slot
+                        .as_mut()
+                        .ok_or(kerr!(ErrKind::SlotIsEmpty))
+                        .and_then(|entry| entry.as_capability::<Untyped>())
+                        .for_arg(3)?;
+                    asid_control_cap.make_pool(untyped_entry, dest_slot)?;
+                    Ok(None)
+                }
+                _ => Err(kerr!(ErrKind::UnknownInvocation)),
+            }
+        }
+        CapabilityType::AsidPool => Err(kerr!(ErrKind::UnknownInvocation)),
+        CapabilityType::Benchmark => {
+            let benchmark_cap = slot.cap_ref_mut().try_ref_mut_as::<Benchmark>()?;
+            match inv_label {
+                InvLabel::BenchmarkConfigCounter => {
+                    let event_idx = reg.a3;
+                    let event_data = reg.a4;
+                    let counter_idx = benchmark_cap
+                        .config_counter(event_idx, event_data)
+                        .for_arg(3)?;
+                    Ok(Some(counter_idx))
+                }
+                InvLabel::BenchmarkStartCounter => {
+                    let counter_idx = reg.a3;
+                    benchmark_cap
+                        .start_counter(counter_idx, reg.a4)
+                        .for_arg(3)?;
+                    Ok(None)
+                }
+                InvLabel::BenchmarkStopCounter => {
+                    let counter_idx = reg.a3;
+                    benchmark_cap.stop_counter(counter_idx).for_arg(3)?;
+                    Ok(None)
+                }
+                InvLabel::BenchmarkReadCounter => {
+                    let counter_idx = reg.a3;
+                    let counter_value = benchmark_cap.read_counter(counter_idx).for_arg(3)?;
+                    ipc_buffer
+                        .ok_or(kerr!(ErrKind::InvalidOperation))?
+                        .write_as(|| counter_value)
+                        .map_err(|e_kind| kerr!(e_kind))?;
+                    Ok(None)
+                }
+                InvLabel::BenchmarkReleaseCounter => {
+                    let counter_idx = reg.a3;
+                    benchmark_cap.release_counter(counter_idx).for_arg(3)?;
+                    Ok(None)
+                }
+                _ => Err(kerr!(ErrKind::UnknownInvocation)),
+            }
+        }
+        CapabilityType::SchedControl => {
+            let sched_control_cap = slot.cap_ref_mut().try_ref_mut_as::<SchedControl>()?;
+            match inv_label {
+                InvLabel::SchedControlGetIdleTime => {
+                    let idle_time = sched_control_cap.get_idle_time(reg.a3).for_arg(3)?;
+                    ipc_buffer
+                        .ok_or(kerr!(ErrKind::InvalidOperation))?
+                        .write_as(|| idle_time)
+                        .map_err(|e_kind| kerr!(e_kind))?;
                     Ok(None)
                 }
                 _ => Err(kerr!(ErrKind::UnknownInvocation)),
@@ -299,3 +526,22 @@ fn handle_invocation(
         }
     }
 }
+
+/// Look up capability given by argument registers reg_idx (cap ptr) and reg_idx + 1 (depth).
+/// Errors are tagged with reg_idx, so that user can tell which argument was wrong.
+fn lookup_cap<K>(
+    root_cnode: &mut CNodeCap,
+    cap_ptr: usize,
+    depth: u32,
+    reg_idx: usize,
+) -> KernelResult<&mut CNodeEntry<K>>
+where
+    K: KObject,
+    CapabilityData<K>: Capability,
+{
+    root_cnode
+        .lookup_entry_mut(cap_ptr, depth)
+        .and_then(|slot| slot.as_mut().ok_or(kerr!(ErrKind::SlotIsEmpty)))
+        .and_then(|entry| entry.as_capability::<K>())
+        .for_arg(reg_idx)
+}
@@ -0,0 +1,47 @@
+use crate::capability::CapabilityType;
+use crate::common::ErrKind;
+use crate::riscv::r_time;
+
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+use shared::trace::{RawTraceEvent, TraceBuffer};
+
+/// Set at initialization, events before it are dropped.
+static TRACE_BUFFER: AtomicPtr<TraceBuffer> = AtomicPtr::new(ptr::null_mut());
+/// Cap type invoked by current syscall, 0 if it is not known yet.
+static SYSCALL_CAP_TYPE: AtomicU8 = AtomicU8::new(0);
+
+pub fn init_trace(buffer: &'static mut TraceBuffer) {
+    TRACE_BUFFER.store(buffer, Ordering::Release);
+}
+
+fn record(event: RawTraceEvent) {
+    let buffer = TRACE_BUFFER.load(Ordering::Acquire);
+    if let Some(buffer) = unsafe { buffer.as_mut() } {
+        buffer.record(event)
+    }
+}
+
+pub fn trace_trap(scause: usize, sepc: usize) {
+    SYSCALL_CAP_TYPE.store(0, Ordering::Relaxed);
+    record(RawTraceEvent::trap(r_time(), scause, sepc))
+}
+
+pub fn trace_cap_type(cap_type: CapabilityType) {
+    SYSCALL_CAP_TYPE.store(cap_type as u8, Ordering::Relaxed);
+}
+
+pub fn trace_syscall(syscall_n: usize, inv_label: usize, err: Option<ErrKind>) {
+    let cap_type = CapabilityType::try_from(SYSCALL_CAP_TYPE.load(Ordering::Relaxed)).ok();
+    record(RawTraceEvent::syscall(
+        r_time(),
+        syscall_n,
+        inv_label,
+        cap_type,
+        err,
+    ))
+}
+
+pub fn trace_context_switch(from_tid: usize, to_tid: usize) {
+    record(RawTraceEvent::context_switch(r_time(), from_tid, to_tid))
+}
@@ -1,23 +1,135 @@
 use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::sbi;
+use crate::memlayout::KERNEL_CODE_PFX;
+use crate::sbi::{sbi_console_putchar, sbi_debug_console_write, SBI_ERR_NOT_SUPPORTED};
+
+const CONSOLE_BUF_SIZE: usize = 16 * 1024;
+
+/// Output is buffered here and drained on timer tick and in idle thread,
+/// so that printing doesn't poll uart in the middle of syscalls.
+struct Console {
+    buf: [u8; CONSOLE_BUF_SIZE],
+    /// number of bytes written since boot, next byte is at buf[head % CONSOLE_BUF_SIZE].
+    head: usize,
+    /// number of bytes drained since boot.
+    tail: usize,
+}
+
+static mut CONSOLE: Console = Console {
+    buf: [0; CONSOLE_BUF_SIZE],
+    head: 0,
+    tail: 0,
+};
+static CONSOLE_LOCKED: AtomicBool = AtomicBool::new(false);
+
+impl Console {
+    /// Drain synchronously only if buffer is full, so that nothing is lost.
+    fn push(&mut self, ch: u8) {
+        if self.head - self.tail == CONSOLE_BUF_SIZE {
+            self.drain();
+        }
+        self.buf[self.head % CONSOLE_BUF_SIZE] = ch;
+        self.head += 1;
+    }
+
+    fn drain(&mut self) {
+        while self.tail != self.head {
+            let start = self.tail % CONSOLE_BUF_SIZE;
+            let len = (self.head - self.tail).min(CONSOLE_BUF_SIZE - start);
+            let chunk = &self.buf[start..start + len];
+            // buffer is in kernel image, and DBCN takes physical address.
+            let paddr = chunk.as_ptr() as usize & !KERNEL_CODE_PFX;
+            let ret = sbi_debug_console_write(len, paddr, 0);
+            let written = match ret.error {
+                0 if ret.value != 0 => ret.value as usize,
+                // legacy putchar is used also when nothing was written, not to loop forever.
+                0 | SBI_ERR_NOT_SUPPORTED => {
+                    for ch in chunk {
+                        sbi_console_putchar(*ch);
+                    }
+                    len
+                }
+                _ => return,
+            };
+            self.tail += written;
+        }
+    }
+}
+
+fn try_lock_console() -> Option<&'static mut Console> {
+    CONSOLE_LOCKED
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .ok()
+        .map(|_| unsafe { &mut *ptr::addr_of_mut!(CONSOLE) })
+}
+
+fn lock_console() -> &'static mut Console {
+    loop {
+        if let Some(console) = try_lock_console() {
+            return console;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+fn unlock_console() {
+    CONSOLE_LOCKED.store(false, Ordering::Release);
+}
 
 pub fn putchar(ch: u8) {
-    sbi::sbi_call(ch as i64, 0, 0, 0, 0, 0, 0, 1);
+    lock_console().push(ch);
+    unlock_console();
+}
+
+/// Write buffered output to uart, it does nothing if other hart is draining.
+pub fn drain_console() {
+    if let Some(console) = try_lock_console() {
+        console.drain();
+        unlock_console();
+    }
+}
+
+/// Write all buffered output to uart, used before panic and shutdown.
+pub fn flush_console() {
+    lock_console().drain();
+    unlock_console();
 }
 
 pub struct Uart;
 
 impl fmt::Write for Uart {
     fn write_str(&mut self, s: &str) -> fmt::Result {
+        let console = lock_console();
         for ch in s.as_bytes() {
-            putchar(*ch)
+            console.push(*ch)
         }
+        unlock_console();
+        Ok(())
+    }
+}
+
+/// Write to uart without the buffer, so it works even if console is locked.
+pub struct RawUart;
 
+impl fmt::Write for RawUart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.as_bytes() {
+            sbi_console_putchar(*ch);
+        }
         Ok(())
     }
 }
 
+/// Used by panic handler, because panic may happen while console is locked.
+/// Buffered output is written first only if console is not locked.
+pub fn panic_print(args: fmt::Arguments) {
+    drain_console();
+    use fmt::Write;
+    let _ = RawUart.write_fmt(args);
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     let mut uart = Uart;
@@ -0,0 +1,90 @@
+use core::arch::asm;
+
+use crate::caps::EndpointCapability;
+use crate::syscall::SysCallFailed;
+
+#[inline(always)]
+pub fn rdcycle() -> usize {
+    let ret;
+    unsafe { asm!("rdcycle {}", out(reg) ret) };
+    ret
+}
+
+#[inline(always)]
+pub fn rdtime() -> usize {
+    let ret;
+    unsafe { asm!("rdtime {}", out(reg) ret) };
+    ret
+}
+
+#[inline(always)]
+pub fn rdinstret() -> usize {
+    let ret;
+    unsafe { asm!("rdinstret {}", out(reg) ret) };
+    ret
+}
+
+/// Statistics of measured values, in unit of the counter used.
+#[derive(Debug, Clone, Copy)]
+pub struct Measurement {
+    pub iterations: usize,
+    pub min: usize,
+    pub max: usize,
+    pub total: usize,
+}
+
+impl Measurement {
+    pub fn average(&self) -> usize {
+        self.total / self.iterations.max(1)
+    }
+}
+
+/// Run f iterations times and measure each run with counter (for example rdcycle).
+pub fn measure<E>(
+    iterations: usize,
+    counter: fn() -> usize,
+    mut f: impl FnMut() -> Result<(), E>,
+) -> Result<Measurement, E> {
+    let mut measurement = Measurement {
+        iterations,
+        min: usize::MAX,
+        max: 0,
+        total: 0,
+    };
+    for _ in 0..iterations {
+        let start = counter();
+        f()?;
+        let elapsed = counter().wrapping_sub(start);
+        measurement.min = measurement.min.min(elapsed);
+        measurement.max = measurement.max.max(elapsed);
+        measurement.total += elapsed;
+    }
+    Ok(measurement)
+}
+
+/// Measure round trip latency of ipc in cycles.
+/// Other thread should run ipc_echo with same iterations on the other endpoint.
+pub fn ipc_round_trip(
+    iterations: usize,
+    send_ep: &EndpointCapability,
+    recv_ep: &EndpointCapability,
+) -> Result<Measurement, SysCallFailed> {
+    measure(iterations, rdcycle, || {
+        send_ep.send()?;
+        recv_ep.recive()?;
+        Ok(())
+    })
+}
+
+/// Partner of ipc_round_trip.
+pub fn ipc_echo(
+    iterations: usize,
+    recv_ep: &EndpointCapability,
+    send_ep: &EndpointCapability,
+) -> Result<(), SysCallFailed> {
+    for _ in 0..iterations {
+        recv_ep.recive()?;
+        send_ep.send()?;
+    }
+    Ok(())
+}
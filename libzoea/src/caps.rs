@@ -1,14 +1,23 @@
 use crate::{
     syscall::{
-        cnode_copy, cnode_mint, configure_tcb, make_page_table_root, map_page, map_page_table,
-        recv_ipc, recv_signal, resume_tcb, send_ipc, send_signal, set_ipc_buffer, unmap_page,
+        asid_control_make_pool, benchmark_config_counter, benchmark_read_counter,
+        benchmark_release_counter, benchmark_start_counter, benchmark_stop_counter,
+        bind_death_notification, cnode_copy, cnode_mint, cnode_rotate, cnode_swap, configure_tcb,
+        copy_registers, get_page_address, get_tid, make_page_table_root, map_page, map_page_table,
+        read_reg, recv_ipc, recv_signal, remap_page, resume_tcb, sched_control_get_idle_time,
+        send_ipc, send_signal, set_ipc_buffer, set_tls_base, suspend_tcb, unmap_page,
         untyped_retype, write_reg, SysCallFailed,
     },
     IPCBuffer,
 };
 
-use shared::{cap_type::CapabilityType, err_kind::ErrKind};
-use shared::{registers::Registers, types::UntypedInfo};
+use core::marker::PhantomData;
+
+use shared::{cap_type::CapabilityType, err_kind::ErrKind, page_size::PageSize};
+use shared::{
+    registers::Registers,
+    types::{cnode_guard_from_value, CounterValue, IdleTime, UntypedInfo},
+};
 
 pub trait KernelObject {
     const CAP_TYPE: CapabilityType;
@@ -40,6 +49,8 @@ pub struct Capability<K: KernelObject> {
 pub struct Untyped {
     pub is_device: bool,
     pub size_bits: usize,
+    /// Offset of free memory reported by kernel on the last retype through this capability.
+    pub free_offset: usize,
 }
 
 impl KernelObject for Untyped {
@@ -48,6 +59,7 @@ impl KernelObject for Untyped {
         Self {
             size_bits: user_size,
             is_device,
+            free_offset: 0,
         }
     }
 }
@@ -55,6 +67,9 @@ impl KernelObject for Untyped {
 #[derive(Debug, Default, Clone, Copy)]
 pub struct CNode {
     pub radix: u32,
+    /// guard_size bits of cap ptr which must be equal to guard before radix bits.
+    pub guard: usize,
+    pub guard_size: u32,
     // TODO: We have to track which slots are now in using.
     // Box<[Option<&Cap<Something>; 2_usize.pow(self.radix)]
     // or simple bitmap
@@ -67,11 +82,25 @@ impl KernelObject for CNode {
     fn from_retype(user_size: usize, _is_device: bool) -> Self {
         Self {
             radix: user_size as u32,
+            guard: 0,
+            guard_size: 0,
             cursor: 0,
         }
     }
 }
 
+// value is made by shared::types::cnode_guard.
+impl Mintable for CNode {
+    fn mint_data(&self, value: usize) -> Self {
+        let (guard, guard_size) = cnode_guard_from_value(value);
+        Self {
+            guard,
+            guard_size,
+            ..*self
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct PageTable {
     pub mapped_address: usize,
@@ -94,20 +123,26 @@ impl FixedSizeObject for PageTable {
     const OBJECT_SIZE: usize = 4096;
 }
 
+/// Right to get physical address of page frame,
+/// newly retyped frame or given by kernel at boot has it, and it can be dropped by mint.
+pub const PAGE_RIGHT_DMA: usize = 0x08;
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Page {
     pub mapped_address: usize,
     pub is_mapped: bool,
     pub rights: PageFlags,
+    pub size: PageSize,
 }
 
 impl KernelObject for Page {
     const CAP_TYPE: CapabilityType = CapabilityType::Page;
-    fn from_retype(_user_size: usize, _is_device: bool) -> Self {
+    fn from_retype(user_size: usize, _is_device: bool) -> Self {
         Self {
             mapped_address: 0,
             is_mapped: false,
             rights: PageFlags::never(),
+            size: PageSize::try_from(user_size).unwrap_or_default(),
         }
     }
 }
@@ -122,6 +157,59 @@ impl Copyable for Page {
     }
 }
 
+// value is rights of new cap, which will be masked by rights of original cap.
+// rights are unix style (exec 1, write 2, read 4) and PAGE_RIGHT_DMA.
+impl Mintable for Page {
+    fn mint_data(&self, _value: usize) -> Self {
+        Self {
+            mapped_address: 0,
+            is_mapped: false,
+            rights: PageFlags::never(),
+            size: self.size,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AsidControl {}
+
+impl KernelObject for AsidControl {
+    const CAP_TYPE: CapabilityType = CapabilityType::AsidControl;
+    fn from_retype(_user_size: usize, _is_device: bool) -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SchedControl {}
+
+impl KernelObject for SchedControl {
+    const CAP_TYPE: CapabilityType = CapabilityType::SchedControl;
+    fn from_retype(_user_size: usize, _is_device: bool) -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Benchmark {}
+
+impl KernelObject for Benchmark {
+    const CAP_TYPE: CapabilityType = CapabilityType::Benchmark;
+    fn from_retype(_user_size: usize, _is_device: bool) -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AsidPool {}
+
+impl KernelObject for AsidPool {
+    const CAP_TYPE: CapabilityType = CapabilityType::AsidPool;
+    fn from_retype(_user_size: usize, _is_device: bool) -> Self {
+        Self {}
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Endpoint {}
 
@@ -194,6 +282,7 @@ impl UntypedCapability {
             cap_data: Untyped {
                 is_device: info.is_device,
                 size_bits: info.bits,
+                free_offset: 0,
             },
         }
     }
@@ -204,7 +293,7 @@ impl UntypedCapability {
         user_size: usize,
     ) -> Result<Capability<T>, SysCallFailed> {
         let num = 1;
-        untyped_retype(
+        let result = untyped_retype(
             self.cap_ptr,
             self.cap_depth,
             slot.pptr,
@@ -214,6 +303,7 @@ impl UntypedCapability {
             num,
             T::CAP_TYPE,
         )?;
+        self.cap_data.free_offset = result.free_offset;
         let new_c = T::from_retype(user_size, self.cap_data.is_device);
         // We have to caluculate new cap postion.
         let (cap_ptr, cap_depth) = slot.get_cap_ptr();
@@ -224,32 +314,109 @@ impl UntypedCapability {
         })
     }
 
+    /// Retype num objects into consecutive slots of cnode at once.
+    /// Kernel may create fewer objects from RAM, then remaining reserved slots are left empty.
+    pub fn retype<T: KernelObject>(
+        &mut self,
+        cnode: &mut CNodeCapability,
+        user_size: usize,
+        num: usize,
+    ) -> Result<Retyped<T>, SysCallFailed> {
+        let first = cnode.get_slots(num)?;
+        let result = untyped_retype(
+            self.cap_ptr,
+            self.cap_depth,
+            first.pptr,
+            first.depth,
+            first.index,
+            user_size as u32,
+            num as u32,
+            T::CAP_TYPE,
+        )?;
+        self.cap_data.free_offset = result.free_offset;
+        Ok(Retyped {
+            first,
+            next: 0,
+            num: result.num,
+            user_size,
+            is_device: self.cap_data.is_device,
+            _phantom: PhantomData,
+        })
+    }
+
+    pub fn retype_page(
+        &mut self,
+        slot: &mut CSlot,
+        size: PageSize,
+    ) -> Result<PageCapability, SysCallFailed> {
+        self.retype_single::<Page>(slot, size.bytes())
+    }
+
     pub fn retype_single_with_fixed_size<T: FixedSizeObject>(
         &mut self,
         slot: &mut CSlot,
     ) -> Result<Capability<T>, SysCallFailed> {
-        // NOTE: user_size will be ignored in kernel.
         let user_size = T::OBJECT_SIZE;
         self.retype_single::<T>(slot, user_size)
     }
 }
 
+/// Capabilities created by UntypedCapability::retype, in order of slots.
+#[derive(Debug)]
+pub struct Retyped<T: KernelObject> {
+    first: CSlot,
+    next: usize,
+    num: usize,
+    user_size: usize,
+    is_device: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: KernelObject> Iterator for Retyped<T> {
+    type Item = Capability<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.num {
+            return None;
+        }
+        let slot = CSlot {
+            index: self.first.index + self.next as u32,
+            ..self.first
+        };
+        self.next += 1;
+        let (cap_ptr, cap_depth) = slot.get_cap_ptr();
+        Some(Capability {
+            cap_ptr,
+            cap_depth,
+            cap_data: T::from_retype(self.user_size, self.is_device),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct CSlot {
     // Parent CNode path
     pptr: usize,
     depth: u32,
-    // Parent radix
+    // Parent radix and guard
     radix: u32,
+    guard: usize,
+    guard_size: u32,
     index: u32,
 }
 
 impl CSlot {
+    /// Cap ptr and depth of the slot from parent CNode.
+    pub fn get_index_in_parent(&self) -> (usize, u32) {
+        let index = (self.guard << self.radix) + self.index as usize;
+        (index, self.guard_size + self.radix)
+    }
+
     pub fn get_cap_ptr(&self) -> (usize, u32) {
         // TODO: check overflow
         // TODO: If this is from root cnode, we don't have to add
-        let new_depth = self.depth + self.radix;
-        let new_pptr = (self.pptr << self.radix) + self.index as usize;
+        let new_depth = self.depth + self.guard_size + self.radix;
+        let new_pptr =
+            (((self.pptr << self.guard_size) | self.guard) << self.radix) + self.index as usize;
         (new_pptr, new_depth)
     }
 }
@@ -260,12 +427,14 @@ impl CNodeCapability {
     pub fn get_slot(&mut self) -> Result<CSlot, SysCallFailed> {
         let size = self.get_size();
         if self.cap_data.cursor >= size {
-            Err((ErrKind::NoEnoughSlot, 0))
+            Err((ErrKind::NoEnoughSlot.into(), 0))
         } else {
             let ret = Ok(CSlot {
                 pptr: self.cap_ptr,
                 depth: self.cap_depth,
                 radix: self.cap_data.radix,
+                guard: self.cap_data.guard,
+                guard_size: self.cap_data.guard_size,
                 index: self.cap_data.cursor as u32,
             });
             self.cap_data.cursor += 1;
@@ -273,6 +442,19 @@ impl CNodeCapability {
         }
     }
 
+    /// Reserve num consecutive slots, and return the first one.
+    pub fn get_slots(&mut self, num: usize) -> Result<CSlot, SysCallFailed> {
+        if num == 0 {
+            return Err((ErrKind::InvalidOperation.into(), 0));
+        }
+        if self.cap_data.cursor + num > self.get_size() {
+            return Err((ErrKind::NoEnoughSlot.into(), 0));
+        }
+        let first = self.get_slot()?;
+        self.cap_data.cursor += num - 1;
+        Ok(first)
+    }
+
     pub fn get_size(&self) -> usize {
         2_usize.pow(self.cap_data.radix)
     }
@@ -282,8 +464,7 @@ impl CNodeCapability {
         cap: &Capability<K>,
     ) -> Result<Capability<K>, SysCallFailed> {
         let slot = self.get_slot()?;
-        let depth = slot.radix;
-        let index = slot.index as usize;
+        let (index, depth) = slot.get_index_in_parent();
         let (dest_ptr, dest_depth) = slot.get_cap_ptr();
         cnode_copy(
             self.cap_ptr,
@@ -331,6 +512,51 @@ impl CNodeCapability {
             cap_data,
         })
     }
+
+    /// Exchange slots of a and b atomically, b must be in this cnode.
+    pub fn swap<K1: KernelObject, K2: KernelObject>(
+        &self,
+        a: &mut Capability<K1>,
+        b: &mut Capability<K2>,
+    ) -> Result<(), SysCallFailed> {
+        cnode_swap(
+            self.cap_ptr,
+            self.cap_depth,
+            b.cap_ptr,
+            b.cap_depth,
+            a.cap_ptr,
+            a.cap_depth,
+        )?;
+        core::mem::swap(&mut a.cap_ptr, &mut b.cap_ptr);
+        core::mem::swap(&mut a.cap_depth, &mut b.cap_depth);
+        Ok(())
+    }
+
+    /// Move pivot to new slot of this cnode and src to old slot of pivot atomically.
+    pub fn rotate<K1: KernelObject, K2: KernelObject>(
+        &mut self,
+        src: &mut Capability<K1>,
+        pivot: &mut Capability<K2>,
+    ) -> Result<(), SysCallFailed> {
+        let slot = self.get_slot()?;
+        let (index, depth) = slot.get_index_in_parent();
+        let (dest_ptr, dest_depth) = slot.get_cap_ptr();
+        cnode_rotate(
+            self.cap_ptr,
+            self.cap_depth,
+            index,
+            depth,
+            src.cap_ptr,
+            src.cap_depth,
+            pivot.cap_ptr,
+            pivot.cap_depth,
+        )?;
+        src.cap_ptr = pivot.cap_ptr;
+        src.cap_depth = pivot.cap_depth;
+        pivot.cap_ptr = dest_ptr;
+        pivot.cap_depth = dest_depth;
+        Ok(())
+    }
 }
 
 pub type PageTableCapability = Capability<PageTable>;
@@ -346,8 +572,16 @@ impl PageTableCapability {
         )
     }
 
-    pub fn make_as_root(&mut self) -> Result<(), SysCallFailed> {
-        make_page_table_root(self.cap_ptr, self.cap_depth)?;
+    pub fn make_as_root(
+        &mut self,
+        asid_pool: &mut AsidPoolCapability,
+    ) -> Result<(), SysCallFailed> {
+        make_page_table_root(
+            self.cap_ptr,
+            self.cap_depth,
+            asid_pool.cap_ptr,
+            asid_pool.cap_depth,
+        )?;
         self.cap_data.is_mapped = true;
         Ok(())
     }
@@ -356,6 +590,10 @@ impl PageTableCapability {
 pub type PageCapability = Capability<Page>;
 
 impl PageCapability {
+    pub fn page_size(&self) -> PageSize {
+        self.cap_data.size
+    }
+
     pub fn map(
         &mut self,
         root_table: &mut PageTableCapability,
@@ -376,25 +614,45 @@ impl PageCapability {
         Ok(())
     }
 
-    pub fn unmap(&mut self, root_table: &mut PageTableCapability) -> Result<(), SysCallFailed> {
-        unmap_page(
-            self.cap_ptr,
-            self.cap_depth,
-            root_table.cap_ptr,
-            root_table.cap_depth,
-        )?;
+    pub fn unmap(&mut self) -> Result<(), SysCallFailed> {
+        unmap_page(self.cap_ptr, self.cap_depth)?;
         self.cap_data.is_mapped = false;
         self.cap_data.mapped_address = 0;
         self.cap_data.rights = PageFlags::never();
         Ok(())
     }
+
+    /// physical address of the frame, cap must have PAGE_RIGHT_DMA.
+    pub fn get_address(&self) -> Result<usize, SysCallFailed> {
+        get_page_address(self.cap_ptr, self.cap_depth)
+    }
+
+    /// change flags of current mapping without unmapping it.
+    pub fn remap(&mut self, flags: PageFlags) -> Result<(), SysCallFailed> {
+        remap_page(self.cap_ptr, self.cap_depth, flags.into())?;
+        self.cap_data.rights = flags;
+        Ok(())
+    }
 }
 
+/// There is no global flag. Global TLB entries ignore ASID and stay visible to other
+/// address spaces, so kernel maps user pages without it and drops bit 3 of flags.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct PageFlags {
     pub is_writable: bool,
     pub is_readable: bool,
     pub is_executable: bool,
+    pub memory_type: MemoryType,
+}
+
+/// Memory attribute of mapping, which needs Svpbmt on the platform.
+/// Without it, attribute of physical memory is used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryType {
+    #[default]
+    Normal = 0,
+    NonCacheable = 1,
+    Io = 2,
 }
 
 impl From<PageFlags> for usize {
@@ -402,7 +660,8 @@ impl From<PageFlags> for usize {
         let write = if value.is_writable { 0x02 } else { 0x00 };
         let read = if value.is_readable { 0x04 } else { 0x00 };
         let exec = if value.is_executable { 0x01 } else { 0x00 };
-        write | read | exec
+        let memory_type = (value.memory_type as usize) << 4;
+        write | read | exec | memory_type
     }
 }
 
@@ -412,6 +671,7 @@ impl PageFlags {
             is_writable: false,
             is_readable: true,
             is_executable: false,
+            ..Default::default()
         }
     }
 
@@ -420,6 +680,7 @@ impl PageFlags {
             is_writable: true,
             is_readable: false,
             is_executable: false,
+            ..Default::default()
         }
     }
 
@@ -428,6 +689,7 @@ impl PageFlags {
             is_writable: false,
             is_readable: true,
             is_executable: true,
+            ..Default::default()
         }
     }
 
@@ -436,6 +698,7 @@ impl PageFlags {
             is_writable: true,
             is_readable: true,
             is_executable: true,
+            ..Default::default()
         }
     }
 
@@ -444,6 +707,14 @@ impl PageFlags {
             is_writable: true,
             is_readable: true,
             is_executable: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_memory_type(self, memory_type: MemoryType) -> Self {
+        Self {
+            memory_type,
+            ..self
         }
     }
 
@@ -452,10 +723,91 @@ impl PageFlags {
             is_writable: false,
             is_readable: false,
             is_executable: false,
+            ..Default::default()
         }
     }
 }
 
+pub type AsidControlCapability = Capability<AsidControl>;
+
+impl AsidControlCapability {
+    pub fn make_pool(
+        &mut self,
+        untyped: &mut UntypedCapability,
+        slot: &mut CSlot,
+    ) -> Result<AsidPoolCapability, SysCallFailed> {
+        let (cap_ptr, cap_depth) = slot.get_cap_ptr();
+        asid_control_make_pool(
+            self.cap_ptr,
+            self.cap_depth,
+            untyped.cap_ptr,
+            untyped.cap_depth,
+            cap_ptr,
+            cap_depth,
+        )?;
+        Ok(Capability {
+            cap_ptr,
+            cap_depth,
+            cap_data: AsidPool {},
+        })
+    }
+}
+
+pub type AsidPoolCapability = Capability<AsidPool>;
+
+pub type SchedControlCapability = Capability<SchedControl>;
+
+impl SchedControlCapability {
+    /// Return idle time and up time of the hart, see IdleTime.
+    pub fn get_idle_time(
+        &self,
+        hartid: usize,
+        ipc_buffer: &IPCBuffer,
+    ) -> Result<IdleTime, SysCallFailed> {
+        sched_control_get_idle_time(self.cap_ptr, self.cap_depth, hartid, ipc_buffer)
+    }
+}
+
+pub type BenchmarkCapability = Capability<Benchmark>;
+
+impl BenchmarkCapability {
+    /// Configure a counter for the SBI PMU event, and return index of it.
+    pub fn config_counter(
+        &self,
+        event_idx: usize,
+        event_data: usize,
+    ) -> Result<usize, SysCallFailed> {
+        benchmark_config_counter(self.cap_ptr, self.cap_depth, event_idx, event_data)
+    }
+
+    pub fn start_counter(
+        &self,
+        counter_idx: usize,
+        initial_value: usize,
+    ) -> Result<(), SysCallFailed> {
+        benchmark_start_counter(self.cap_ptr, self.cap_depth, counter_idx, initial_value)?;
+        Ok(())
+    }
+
+    pub fn stop_counter(&self, counter_idx: usize) -> Result<(), SysCallFailed> {
+        benchmark_stop_counter(self.cap_ptr, self.cap_depth, counter_idx)?;
+        Ok(())
+    }
+
+    pub fn read_counter(
+        &self,
+        counter_idx: usize,
+        ipc_buffer: &IPCBuffer,
+    ) -> Result<CounterValue, SysCallFailed> {
+        benchmark_read_counter(self.cap_ptr, self.cap_depth, counter_idx, ipc_buffer)
+    }
+
+    pub fn release_counter(&self, counter_idx: usize) -> Result<(), SysCallFailed> {
+        benchmark_release_counter(self.cap_ptr, self.cap_depth, counter_idx)?;
+        Ok(())
+    }
+}
+
 pub type TCBCapability = Capability<ThreadControlBlock>;
 
 impl TCBCapability {
@@ -478,6 +830,45 @@ impl TCBCapability {
         Ok(())
     }
 
+    /// Read registers of not runnable thread through ipc buffer.
+    pub fn read_regs(&self, ipc_buffer: &IPCBuffer) -> Result<Registers, SysCallFailed> {
+        read_reg(self.cap_ptr, self.cap_depth, ipc_buffer)
+    }
+
+    /// Set tp of the thread, see tls module for TLS block.
+    pub fn set_tls_base(&mut self, tls_base: usize) -> Result<(), SysCallFailed> {
+        set_tls_base(self.cap_ptr, self.cap_depth, tls_base)?;
+        Ok(())
+    }
+
+    /// Name is used only by kernel messages for debugging.
+    #[cfg(feature = "debug-syscalls")]
+    pub fn set_name(&mut self, name: &str) -> Result<(), SysCallFailed> {
+        crate::syscall::debug_name_thread(self.cap_ptr, self.cap_depth, name)?;
+        Ok(())
+    }
+
+    pub fn get_id(&self) -> Result<usize, SysCallFailed> {
+        get_tid(self.cap_ptr, self.cap_depth)
+    }
+
+    /// Copy registers of src to this thread.
+    /// If src is the caller, this thread starts after the invocation and gets 0,
+    /// while the caller gets 1.
+    pub fn copy_regs_from(
+        &mut self,
+        src: &TCBCapability,
+        flags: usize,
+    ) -> Result<usize, SysCallFailed> {
+        copy_registers(
+            self.cap_ptr,
+            self.cap_depth,
+            src.cap_ptr,
+            src.cap_depth,
+            flags,
+        )
+    }
+
     pub fn configure(
         &mut self,
         root_cnode: &mut CNodeCapability,
@@ -498,6 +889,34 @@ impl TCBCapability {
         resume_tcb(self.cap_ptr, self.cap_depth)?;
         Ok(())
     }
+
+    pub fn suspend(&mut self) -> Result<(), SysCallFailed> {
+        suspend_tcb(self.cap_ptr, self.cap_depth)?;
+        Ok(())
+    }
+
+    /// Notification is signaled with its badge when the thread stops.
+    pub fn bind_death_notification(
+        &mut self,
+        notification: &NotificaitonCapability,
+    ) -> Result<(), SysCallFailed> {
+        bind_death_notification(
+            self.cap_ptr,
+            self.cap_depth,
+            notification.cap_ptr,
+            notification.cap_depth,
+        )?;
+        Ok(())
+    }
+
+    /// Wait until the thread stops.
+    /// death_notification must be bound to the thread, and badge of it is returned.
+    pub fn join(
+        &self,
+        death_notification: &NotificaitonCapability,
+    ) -> Result<usize, SysCallFailed> {
+        death_notification.wait()
+    }
 }
 
 pub type EndpointCapability = Capability<Endpoint>;
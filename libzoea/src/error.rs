@@ -0,0 +1,133 @@
+use core::fmt;
+use core::str;
+
+use shared::cap_type::CapabilityType;
+use shared::types::{ErrRecord, ERR_RECORD_FILE_LEN, ERR_RECORD_NONE};
+
+use crate::syscall::{SysCallErr, SysCallFailed};
+use crate::{ErrKind, IPCBuffer};
+
+#[derive(Debug, Clone, Copy)]
+pub struct LookupFailure {
+    /// Number of bits of cap ptr which were resolved.
+    pub resolved_bits: usize,
+    pub bits_left: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CapTypeMismatch {
+    pub expected: Option<CapabilityType>,
+    pub actual: Option<CapabilityType>,
+}
+
+/// Failed syscall with the detail which kernel wrote into IPC buffer.
+#[derive(Clone, Copy)]
+pub struct SysCallError {
+    pub e_kind: SysCallErr,
+    pub e_val: usize,
+    /// Index of argument register, 0 is a0.
+    pub invalid_arg: Option<usize>,
+    pub lookup: Option<LookupFailure>,
+    pub cap_type: Option<CapTypeMismatch>,
+    line: Option<u32>,
+    file_len: u8,
+    file: [u8; ERR_RECORD_FILE_LEN],
+}
+
+fn field(value: usize) -> Option<usize> {
+    (value != ERR_RECORD_NONE).then_some(value)
+}
+
+fn cap_type(value: usize) -> Option<CapabilityType> {
+    CapabilityType::try_from(value as u8).ok()
+}
+
+impl SysCallError {
+    /// err is used if record is broken.
+    pub fn from_record(err: SysCallFailed, record: &ErrRecord) -> Self {
+        let e_kind = ErrKind::try_from(record.e_kind).map_or(err.0, SysCallErr::Kind);
+        let lookup = field(record.failed_depth).map(|resolved_bits| LookupFailure {
+            resolved_bits,
+            bits_left: record.bits_left,
+        });
+        let cap_type = field(record.expected_cap_type).map(|expected| CapTypeMismatch {
+            expected: cap_type(expected),
+            actual: cap_type(record.actual_cap_type),
+        });
+        Self {
+            e_kind,
+            e_val: record.e_val,
+            invalid_arg: field(record.invalid_arg),
+            lookup,
+            cap_type,
+            line: field(record.line).map(|line| line as u32),
+            file_len: record.file_len.min(ERR_RECORD_FILE_LEN) as u8,
+            file: record.file,
+        }
+    }
+
+    /// Detail of err, it should be called right after the failed syscall.
+    pub fn last(err: SysCallFailed, ipc_buffer: &IPCBuffer) -> Self {
+        Self::from_record(err, &ipc_buffer.err_record)
+    }
+
+    /// Source of kernel which returned the error, only if kernel is debug build.
+    pub fn place(&self) -> Option<(&str, u32)> {
+        let file = str::from_utf8(&self.file[..self.file_len as usize]).ok()?;
+        self.line.map(|line| (file, line))
+    }
+}
+
+impl fmt::Debug for SysCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SysCallError")
+            .field("e_kind", &self.e_kind)
+            .field("e_val", &self.e_val)
+            .field("invalid_arg", &self.invalid_arg)
+            .field("lookup", &self.lookup)
+            .field("cap_type", &self.cap_type)
+            .field("place", &self.place())
+            .finish()
+    }
+}
+
+impl fmt::Display for SysCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.e_kind {
+            SysCallErr::Kind(e_kind) => write!(f, "{:?}", e_kind)?,
+            SysCallErr::Unknown(e_kind) => write!(f, "unknown error kind {}", e_kind)?,
+        }
+        if let Some(reg_idx) = self.invalid_arg {
+            write!(f, ", invalid a{}", reg_idx)?;
+        }
+        if let Some(lookup) = self.lookup {
+            write!(
+                f,
+                ", lookup failed after {} bits with {} bits left",
+                lookup.resolved_bits, lookup.bits_left
+            )?;
+        }
+        if let Some(mismatch) = self.cap_type {
+            write!(
+                f,
+                ", expected {:?} but found {:?}",
+                mismatch.expected, mismatch.actual
+            )?;
+        }
+        if let Some((file, line)) = self.place() {
+            write!(f, " at {}:{}", file, line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Attach detail from IPC buffer to result of syscall wrappers.
+pub trait WithDetail<T> {
+    fn detail(self, ipc_buffer: &IPCBuffer) -> Result<T, SysCallError>;
+}
+
+impl<T> WithDetail<T> for Result<T, SysCallFailed> {
+    fn detail(self, ipc_buffer: &IPCBuffer) -> Result<T, SysCallError> {
+        self.map_err(|err| SysCallError::last(err, ipc_buffer))
+    }
+}
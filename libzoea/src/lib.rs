@@ -2,7 +2,6 @@
 
 use core::fmt;
 
-use syscall::put_char;
 pub mod shared;
 
 pub use crate::shared::err_kind::ErrKind;
@@ -12,8 +11,11 @@ pub use crate::shared::syscall_no::SysCallNo;
 pub use crate::shared::types::BootInfo;
 pub use crate::shared::types::IPCBuffer;
 pub use crate::shared::types::UntypedInfo;
+pub mod bench;
 pub mod caps;
+pub mod error;
 pub mod syscall;
+pub mod tls;
 
 #[macro_export]
 macro_rules! print {
@@ -23,13 +25,19 @@ macro_rules! print {
 pub struct SyscallWriter;
 
 impl fmt::Write for SyscallWriter {
+    #[cfg(feature = "debug-syscalls")]
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for ch in s.as_bytes() {
-            let ch = *ch;
-            put_char(ch).unwrap();
+        for chunk in s.as_bytes().chunks(syscall::DEBUG_PUT_STRING_MAX) {
+            syscall::debug_put_string(chunk).unwrap();
         }
         Ok(())
     }
+
+    /// Kernel has no console for user without debug-syscalls, so output is discarded.
+    #[cfg(not(feature = "debug-syscalls"))]
+    fn write_str(&mut self, _s: &str) -> fmt::Result {
+        Ok(())
+    }
 }
 
 #[doc(hidden)]
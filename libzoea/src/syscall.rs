@@ -5,9 +5,27 @@ use crate::Registers;
 use crate::SysCallNo;
 use core::arch::asm;
 pub use shared::cap_type::CapabilityType;
+use shared::types::{CounterValue, IdleTime, RetypeResult};
 
 pub type SysCallRes = Result<usize, SysCallFailed>;
-pub type SysCallFailed = (ErrKind, u16);
+pub type SysCallFailed = (SysCallErr, u16);
+
+/// Error kind of failed syscall, kernel newer than libzoea may return kind unknown to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysCallErr {
+    Kind(ErrKind),
+    Unknown(usize),
+}
+
+impl From<ErrKind> for SysCallErr {
+    fn from(e_kind: ErrKind) -> Self {
+        Self::Kind(e_kind)
+    }
+}
+
+/// Same as limit of kernel.
+#[cfg(feature = "debug-syscalls")]
+pub const DEBUG_PUT_STRING_MAX: usize = 4096;
 
 #[allow(clippy::too_many_arguments)]
 unsafe fn syscall(
@@ -20,14 +38,31 @@ unsafe fn syscall(
     arg6: usize,
     sysno: SysCallNo,
 ) -> SysCallRes {
+    syscall_two_ret(cap_ptr, cap_depth, inv_label, arg3, arg4, arg5, arg6, sysno)
+        .map(|(val, _)| val)
+}
+
+/// Some invocations return second value in a2.
+#[allow(clippy::too_many_arguments)]
+unsafe fn syscall_two_ret(
+    cap_ptr: usize,
+    cap_depth: u32,
+    inv_label: InvLabel,
+    arg3: usize,
+    arg4: usize,
+    arg5: usize,
+    arg6: usize,
+    sysno: SysCallNo,
+) -> Result<(usize, usize), SysCallFailed> {
     let mut is_error: usize;
     let mut val: usize;
+    let mut val2: usize;
 
     asm!(
         "ecall",
         inout("a0") cap_ptr => is_error,
         inout("a1") cap_depth as usize => val,
-        in("a2") inv_label as usize,
+        inout("a2") inv_label as usize => val2,
         in("a3") arg3,
         in("a4") arg4,
         in("a5") arg5,
@@ -36,13 +71,15 @@ unsafe fn syscall(
     );
 
     if is_error == 0 {
-        Ok(val)
+        Ok((val, val2))
     } else {
-        let e_kind = ErrKind::try_from(is_error).unwrap();
+        let e_kind =
+            ErrKind::try_from(is_error).map_or(SysCallErr::Unknown(is_error), SysCallErr::Kind);
         Err((e_kind, val as u16))
     }
 }
 
+#[cfg(feature = "debug-syscalls")]
 pub fn put_char(char: u8) -> SysCallRes {
     unsafe {
         syscall(
@@ -58,6 +95,99 @@ pub fn put_char(char: u8) -> SysCallRes {
     }
 }
 
+/// Stop calling thread, and notification bound to it is signaled.
+pub fn exit() -> ! {
+    loop {
+        // if this thread is resumed, stop again.
+        let _ = unsafe { syscall(0, 0, InvLabel::TcbSuspend, 0, 0, 0, 0, SysCallNo::Exit) };
+    }
+}
+
+/// Print whole bytes in one trap, kernel accepts at most DEBUG_PUT_STRING_MAX bytes at once.
+#[cfg(feature = "debug-syscalls")]
+pub fn debug_put_string(bytes: &[u8]) -> SysCallRes {
+    unsafe {
+        syscall(
+            bytes.as_ptr() as usize,
+            bytes.len() as u32,
+            InvLabel::DebugPutString,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Debug,
+        )
+    }
+}
+
+/// Power off the machine.
+#[cfg(feature = "debug-syscalls")]
+pub fn debug_halt() -> ! {
+    let _ = unsafe { syscall(0, 0, InvLabel::DebugHalt, 0, 0, 0, 0, SysCallNo::Debug) };
+    unreachable!("kernel was not halted")
+}
+
+#[cfg(feature = "debug-syscalls")]
+pub fn debug_dump_scheduler() -> SysCallRes {
+    unsafe {
+        syscall(
+            0,
+            0,
+            InvLabel::DebugDumpScheduler,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Debug,
+        )
+    }
+}
+
+/// Name is shown in kernel messages, and it is truncated to 16 bytes.
+#[cfg(feature = "debug-syscalls")]
+pub fn debug_name_thread(cap_ptr: usize, cap_depth: u32, name: &str) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::DebugNameThread,
+            name.as_ptr() as usize,
+            name.len(),
+            0,
+            0,
+            SysCallNo::Debug,
+        )
+    }
+}
+
+/// Return type of capability in the slot, or None if the slot is empty.
+#[cfg(feature = "debug-syscalls")]
+pub fn debug_cap_identify(
+    cap_ptr: usize,
+    cap_depth: u32,
+) -> Result<Option<CapabilityType>, SysCallFailed> {
+    let cap_type = unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::DebugCapIdentify,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Debug,
+        )?
+    };
+    match cap_type {
+        0 => Ok(None),
+        v => Ok(Some(
+            CapabilityType::try_from(v as u8).map_err(|e_kind| (e_kind.into(), 0))?,
+        )),
+    }
+}
+
+/// Print capabilities in root CNode of calling thread.
+#[cfg(feature = "debug-syscalls")]
 pub fn traverse() -> SysCallRes {
     unsafe { syscall(0, 0, InvLabel::CNodeTraverse, 0, 0, 0, 0, SysCallNo::Print) }
 }
@@ -72,11 +202,11 @@ pub fn untyped_retype(
     user_size: u32,
     num: u32,
     cap_type: CapabilityType,
-) -> SysCallRes {
+) -> Result<RetypeResult, SysCallFailed> {
     let index_and_depth = ((index as usize) << 32) | dest_depth as usize;
     let user_size_and_num = ((user_size as usize) << 32) | num as usize;
-    unsafe {
-        syscall(
+    let (num, free_offset) = unsafe {
+        syscall_two_ret(
             cap_ptr,
             cap_depth,
             InvLabel::UntypedRetype,
@@ -85,8 +215,9 @@ pub fn untyped_retype(
             user_size_and_num,
             cap_type as usize,
             SysCallNo::Call,
-        )
-    }
+        )?
+    };
+    Ok(RetypeResult { num, free_offset })
 }
 
 pub fn write_reg<F>(
@@ -114,6 +245,113 @@ where
     }
 }
 
+pub fn read_reg(
+    cap_ptr: usize,
+    cap_depth: u32,
+    buffer: &IPCBuffer,
+) -> Result<Registers, SysCallFailed> {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::TcbReadRegisters,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )?
+    };
+    Ok(*buffer.read_as::<Registers>().unwrap())
+}
+
+/// flags are COPY_REGS_* in shared::types.
+pub fn copy_registers(
+    cap_ptr: usize,
+    cap_depth: u32,
+    src_ptr: usize,
+    src_depth: u32,
+    flags: usize,
+) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::TcbCopyRegisters,
+            src_ptr,
+            src_depth as usize,
+            flags,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn set_tls_base(cap_ptr: usize, cap_depth: u32, tls_base: usize) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::TcbSetTLSBase,
+            tls_base,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn get_tid(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::TcbGetId,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn suspend_tcb(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::TcbSuspend,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn bind_death_notification(
+    cap_ptr: usize,
+    cap_depth: u32,
+    notify_ptr: usize,
+    notify_depth: u32,
+) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::TcbBindDeathNotification,
+            notify_ptr,
+            notify_depth as usize,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
 pub fn set_ipc_buffer(
     cap_ptr: usize,
     cap_depth: u32,
@@ -201,6 +439,17 @@ pub fn recv_signal(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
     }
 }
 
+/// Pack depths of cnode invocation as | src_depth 32 | pivot_depth 16 | dest_depth 16 |.
+fn cnode_depths(src_depth: u32, pivot_depth: u32, dest_depth: u32) -> SysCallRes {
+    if [src_depth, pivot_depth, dest_depth]
+        .iter()
+        .any(|depth| *depth > usize::BITS)
+    {
+        return Err((ErrKind::InvalidOperation.into(), 4));
+    }
+    Ok(((src_depth as usize) << 32) | ((pivot_depth as usize) << 16) | dest_depth as usize)
+}
+
 pub fn cnode_copy(
     cap_ptr: usize,
     cap_depth: u32,
@@ -209,7 +458,7 @@ pub fn cnode_copy(
     src_index: usize,
     src_depth: u32,
 ) -> SysCallRes {
-    let depth = ((src_depth as usize) << 32) | dest_depth as usize;
+    let depth = cnode_depths(src_depth, 0, dest_depth)?;
     unsafe {
         syscall(
             cap_ptr,
@@ -233,7 +482,7 @@ pub fn cnode_mint(
     src_depth: u32,
     cap_val: usize,
 ) -> SysCallRes {
-    let depth = ((src_depth as usize) << 32) | dest_depth as usize;
+    let depth = cnode_depths(src_depth, 0, dest_depth)?;
     unsafe {
         syscall(
             cap_ptr,
@@ -256,7 +505,7 @@ pub fn cnode_move(
     src_index: usize,
     src_depth: u32,
 ) -> SysCallRes {
-    let depth = ((src_depth as usize) << 32) | dest_depth as usize;
+    let depth = cnode_depths(src_depth, 0, dest_depth)?;
     unsafe {
         syscall(
             cap_ptr,
@@ -293,14 +542,44 @@ pub fn map_page(
     }
 }
 
-pub fn unmap_page(cap_ptr: usize, cap_depth: u32, dest_ptr: usize, dest_depth: u32) -> SysCallRes {
+pub fn unmap_page(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
     unsafe {
         syscall(
             cap_ptr,
             cap_depth,
             InvLabel::PageUnMap,
-            dest_ptr,
-            dest_depth as usize,
+            0,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn remap_page(cap_ptr: usize, cap_depth: u32, flags: usize) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::PageRemap,
+            flags,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn get_page_address(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::PageGetAddress,
+            0,
+            0,
             0,
             0,
             SysCallNo::Call,
@@ -329,14 +608,19 @@ pub fn map_page_table(
     }
 }
 
-pub fn make_page_table_root(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
+pub fn make_page_table_root(
+    cap_ptr: usize,
+    cap_depth: u32,
+    asid_pool_ptr: usize,
+    asid_pool_depth: u32,
+) -> SysCallRes {
     unsafe {
         syscall(
             cap_ptr,
             cap_depth,
             InvLabel::PageTableMakeRoot,
-            0,
-            0,
+            asid_pool_ptr,
+            asid_pool_depth as usize,
             0,
             0,
             SysCallNo::Call,
@@ -344,6 +628,28 @@ pub fn make_page_table_root(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
     }
 }
 
+pub fn asid_control_make_pool(
+    cap_ptr: usize,
+    cap_depth: u32,
+    untyped_ptr: usize,
+    untyped_depth: u32,
+    dest_ptr: usize,
+    dest_depth: u32,
+) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::AsidControlMakePool,
+            untyped_ptr,
+            untyped_depth as usize,
+            dest_ptr,
+            dest_depth as usize,
+            SysCallNo::Call,
+        )
+    }
+}
+
 pub fn send_ipc(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
     unsafe {
         syscall(
@@ -373,3 +679,165 @@ pub fn recv_ipc(cap_ptr: usize, cap_depth: u32) -> SysCallRes {
         )
     }
 }
+
+pub fn sched_control_get_idle_time(
+    cap_ptr: usize,
+    cap_depth: u32,
+    hartid: usize,
+    buffer: &IPCBuffer,
+) -> Result<IdleTime, SysCallFailed> {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::SchedControlGetIdleTime,
+            hartid,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )?
+    };
+    Ok(*buffer.read_as::<IdleTime>().unwrap())
+}
+
+pub fn benchmark_config_counter(
+    cap_ptr: usize,
+    cap_depth: u32,
+    event_idx: usize,
+    event_data: usize,
+) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::BenchmarkConfigCounter,
+            event_idx,
+            event_data,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn benchmark_start_counter(
+    cap_ptr: usize,
+    cap_depth: u32,
+    counter_idx: usize,
+    initial_value: usize,
+) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::BenchmarkStartCounter,
+            counter_idx,
+            initial_value,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn benchmark_stop_counter(cap_ptr: usize, cap_depth: u32, counter_idx: usize) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::BenchmarkStopCounter,
+            counter_idx,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn benchmark_read_counter(
+    cap_ptr: usize,
+    cap_depth: u32,
+    counter_idx: usize,
+    buffer: &IPCBuffer,
+) -> Result<CounterValue, SysCallFailed> {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::BenchmarkReadCounter,
+            counter_idx,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )?
+    };
+    Ok(*buffer.read_as::<CounterValue>().unwrap())
+}
+
+pub fn benchmark_release_counter(cap_ptr: usize, cap_depth: u32, counter_idx: usize) -> SysCallRes {
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::BenchmarkReleaseCounter,
+            counter_idx,
+            0,
+            0,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+pub fn cnode_swap(
+    cap_ptr: usize,
+    cap_depth: u32,
+    dest_index: usize,
+    dest_depth: u32,
+    src_index: usize,
+    src_depth: u32,
+) -> SysCallRes {
+    let depth = cnode_depths(src_depth, 0, dest_depth)?;
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::CNodeSwap,
+            src_index,
+            depth,
+            dest_index,
+            0,
+            SysCallNo::Call,
+        )
+    }
+}
+
+/// pivot is moved to dest, and src is moved to pivot.
+#[allow(clippy::too_many_arguments)]
+pub fn cnode_rotate(
+    cap_ptr: usize,
+    cap_depth: u32,
+    dest_index: usize,
+    dest_depth: u32,
+    src_index: usize,
+    src_depth: u32,
+    pivot_index: usize,
+    pivot_depth: u32,
+) -> SysCallRes {
+    let depth = cnode_depths(src_depth, pivot_depth, dest_depth)?;
+    unsafe {
+        syscall(
+            cap_ptr,
+            cap_depth,
+            InvLabel::CNodeRotate,
+            src_index,
+            depth,
+            dest_index,
+            pivot_index,
+            SysCallNo::Call,
+        )
+    }
+}
@@ -0,0 +1,49 @@
+//! Thread local storage.
+//! On RISC-V, tp points to the start of TLS block, which is a copy of .tdata followed by .tbss.
+//! Symbols below are defined in user.ld.
+use core::arch::asm;
+use core::ptr;
+
+extern "C" {
+    static __tdata_start: u8;
+    static __tdata_end: u8;
+    static __tbss_end: u8;
+    static mut __main_tls: u8;
+}
+
+pub const TLS_ALIGN: usize = 16;
+
+pub fn tls_size() -> usize {
+    &raw const __tbss_end as usize - &raw const __tdata_start as usize
+}
+
+/// Initialise TLS block at block, and return value for tp.
+///
+/// # Safety
+/// block must be aligned to TLS_ALIGN, writable and have tls_size() bytes.
+pub unsafe fn init_tls(block: *mut u8) -> usize {
+    let tdata = &raw const __tdata_start;
+    let tdata_size = &raw const __tdata_end as usize - tdata as usize;
+    ptr::copy_nonoverlapping(tdata, block, tdata_size);
+    ptr::write_bytes(block.add(tdata_size), 0, tls_size() - tdata_size);
+    block as usize
+}
+
+/// Put TLS block at top of stack.
+/// Returns value for tp and new stack top.
+///
+/// # Safety
+/// stack_top must be top of writable stack which is not used yet.
+pub unsafe fn init_tls_on_stack(stack_top: usize) -> (usize, usize) {
+    let base = (stack_top - tls_size()) & !(TLS_ALIGN - 1);
+    (init_tls(base as *mut u8), base)
+}
+
+/// Set up TLS of main thread.
+///
+/// # Safety
+/// Must be called only once at start of program, before using thread locals.
+pub unsafe extern "C" fn init_main_thread() {
+    let tp = init_tls(&raw mut __main_tls);
+    asm!("mv tp, {}", in(reg) tp);
+}
@@ -1,8 +1,18 @@
+use libzoea::caps::AsidControl;
+use libzoea::caps::AsidControlCapability;
+use libzoea::caps::Benchmark;
+use libzoea::caps::BenchmarkCapability;
 use libzoea::caps::CNode;
 use libzoea::caps::CNodeCapability;
+use libzoea::caps::Page;
+use libzoea::caps::PageCapability;
+use libzoea::caps::PageFlags;
 use libzoea::caps::PageTable;
 use libzoea::caps::PageTableCapability;
+use libzoea::caps::SchedControl;
+use libzoea::caps::SchedControlCapability;
 use libzoea::caps::UntypedCapability;
+use libzoea::shared::page_size::PageSize;
 use libzoea::shared::types::BootInfo;
 
 pub const ROOT_CNODE_RADIX: u32 = 18;
@@ -17,6 +27,8 @@ pub fn get_root_cnode(boot_info: &BootInfo) -> CNodeCapability {
         cap_depth: ROOT_CNODE_RADIX,
         cap_data: CNode {
             radix: ROOT_CNODE_RADIX,
+            guard: 0,
+            guard_size: 0,
             cursor: boot_info.firtst_empty_idx,
         },
     }
@@ -33,3 +45,42 @@ pub fn get_root_vspace(boot_info: &BootInfo) -> PageTableCapability {
         },
     }
 }
+
+pub fn get_asid_control(boot_info: &BootInfo) -> AsidControlCapability {
+    AsidControlCapability {
+        cap_ptr: boot_info.asid_control_idx,
+        cap_depth: ROOT_CNODE_RADIX,
+        cap_data: AsidControl {},
+    }
+}
+
+pub fn get_sched_control(boot_info: &BootInfo) -> SchedControlCapability {
+    SchedControlCapability {
+        cap_ptr: boot_info.sched_control_idx,
+        cap_depth: ROOT_CNODE_RADIX,
+        cap_data: SchedControl {},
+    }
+}
+
+pub fn get_benchmark(boot_info: &BootInfo) -> BenchmarkCapability {
+    BenchmarkCapability {
+        cap_ptr: boot_info.benchmark_idx,
+        cap_depth: ROOT_CNODE_RADIX,
+        cap_data: Benchmark {},
+    }
+}
+
+/// Read only frames of kernel trace buffer, empty if kernel is built without trace.
+pub fn get_trace_buffer_pages(boot_info: &BootInfo) -> impl Iterator<Item = PageCapability> {
+    let start = boot_info.trace_buffer_idx;
+    (start..start + boot_info.trace_buffer_pages).map(|cap_ptr| PageCapability {
+        cap_ptr,
+        cap_depth: ROOT_CNODE_RADIX,
+        cap_data: Page {
+            mapped_address: 0,
+            is_mapped: false,
+            rights: PageFlags::readonly(),
+            size: PageSize::Small,
+        },
+    })
+}
@@ -1,10 +1,12 @@
 #![no_std]
 #![no_main]
 #![feature(naked_functions)]
+#![feature(thread_local)]
 
 use core::{arch::naked_asm, panic::PanicInfo};
 
 use libzoea::println;
+use libzoea::tls::init_main_thread;
 
 mod boot_info;
 mod elf;
@@ -21,9 +23,11 @@ extern "C" fn start() {
     unsafe {
         naked_asm!(
         "la sp, {stack_top}",
+        "call {init_tls}",
         "call main",
         "call exit",
-        stack_top = sym __stack_top
+        stack_top = sym __stack_top,
+        init_tls = sym init_main_thread,
         )
     }
 }
@@ -36,5 +40,5 @@ fn panic(info: &PanicInfo) -> ! {
 
 #[no_mangle]
 fn exit() -> ! {
-    panic!("exit")
+    libzoea::syscall::exit()
 }
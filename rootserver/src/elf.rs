@@ -1,15 +1,15 @@
 use core::{cmp::min, ptr};
 use libzoea::{
     caps::{
-        CNodeCapability, Page, PageCapability, PageFlags, PageTable, PageTableCapability,
-        UntypedCapability,
+        AsidPoolCapability, CNodeCapability, Page, PageCapability, PageFlags, PageTable,
+        PageTableCapability, UntypedCapability,
     },
     shared::{
         align_up,
         elf::{def::ProgramFlags, ProgramMapper},
         PAGE_SIZE,
     },
-    syscall::SysCallFailed,
+    syscall::{SysCallErr, SysCallFailed},
     ErrKind,
 };
 
@@ -45,6 +45,7 @@ impl ProgramMapper for ElfProgramMapper<'_> {
             is_writable,
             is_readable,
             is_executable,
+            ..Default::default()
         }
     }
 
@@ -88,12 +89,13 @@ impl<'a> ElfProgramMapper<'a> {
         mut cnode: CNodeCapability,
         mut ut: UntypedCapability,
         root_table: &'a mut PageTableCapability,
+        asid_pool: &mut AsidPoolCapability,
         free_address: usize,
     ) -> Result<Self, SysCallFailed> {
         let mut table_slot = cnode.get_slot()?;
         let mut target_root_vspace =
             ut.retype_single_with_fixed_size::<PageTable>(&mut table_slot)?;
-        target_root_vspace.make_as_root()?;
+        target_root_vspace.make_as_root(asid_pool)?;
         Ok(Self::new(
             cnode,
             ut,
@@ -132,7 +134,7 @@ impl<'a> ElfProgramMapper<'a> {
                 ptr::copy::<u8>(copy_src, self.free_address as *mut u8, copy_size);
             }
             *file_sz_rem = (*file_sz_rem).saturating_sub(PAGE_SIZE);
-            tmp_page_cap.unmap(self.root_table)?;
+            tmp_page_cap.unmap()?;
             self.cnode.delete(tmp_page_cap)?;
         }
         Ok(())
@@ -147,11 +149,11 @@ impl<'a> ElfProgramMapper<'a> {
         let mut page_cap = self.ut.retype_single_with_fixed_size::<Page>(&mut slot)?;
         if let Err(e) = page_cap.map(&mut self.target_root_vspace, vaddr, flags) {
             match e {
-                (ErrKind::PageTableNotMappedYet, value) => {
+                (SysCallErr::Kind(ErrKind::PageTableNotMappedYet), value) => {
                     self.map_page_tables(vaddr, value)?;
                     page_cap.map(&mut self.target_root_vspace, vaddr, flags)?;
                 }
-                _ => Err((ErrKind::InvalidOperation, 0))?,
+                _ => Err((ErrKind::InvalidOperation.into(), 0))?,
             }
         }
         Ok(page_cap)
@@ -1,4 +1,8 @@
+use core::cell::Cell;
+
+use libzoea::bench::{measure, rdcycle};
 use libzoea::caps::CNode;
+use libzoea::caps::CNodeCapability;
 use libzoea::caps::Endpoint;
 use libzoea::caps::EndpointCapability;
 use libzoea::caps::Notificaiton;
@@ -6,19 +10,32 @@ use libzoea::caps::NotificaitonCapability;
 use libzoea::caps::Page;
 use libzoea::caps::PageFlags;
 use libzoea::caps::PageTable;
+use libzoea::caps::PageTableCapability;
 use libzoea::caps::ThreadControlBlock;
+use libzoea::error::WithDetail;
 use libzoea::println;
 use libzoea::shared::aligned_to::AlignedTo;
 use libzoea::shared::elf::def::Elf64Hdr;
+use libzoea::shared::trace::{TraceBuffer, TRACE_BUFFER_LEN};
+use libzoea::syscall::bind_death_notification;
+use libzoea::syscall::exit;
+#[cfg(feature = "debug-syscalls")]
 use libzoea::syscall::traverse;
+use libzoea::tls::init_tls_on_stack;
 use libzoea::BootInfo;
 use libzoea::Registers;
 
-use crate::boot_info::{get_root_cnode, get_root_vspace, get_untyped};
+use crate::boot_info::{
+    get_asid_control, get_benchmark, get_root_cnode, get_root_vspace, get_sched_control,
+    get_trace_buffer_pages, get_untyped,
+};
 use crate::elf::ElfProgramMapper;
 
 pub static mut STACK: [usize; 512] = [0; 512];
 
+#[thread_local]
+static THREAD_LOCAL_CHECK: Cell<usize> = Cell::new(0x1234);
+
 static ALIGNED: &AlignedTo<u8, [u8]> = &AlignedTo {
     _align: [],
     bytes: *include_bytes!("../simple"),
@@ -44,6 +61,10 @@ pub fn main(boot_info: &BootInfo) {
         .retype_single_with_fixed_size::<Notificaiton>(&mut root_cnode.get_slot().unwrap())
         .unwrap();
 
+    let death = untyped
+        .retype_single_with_fixed_size::<Notificaiton>(&mut root_cnode.get_slot().unwrap())
+        .unwrap();
+
     let lv2_cnode = untyped
         .retype_single::<CNode>(&mut root_cnode.get_slot().unwrap(), 18)
         .unwrap();
@@ -75,12 +96,19 @@ pub fn main(boot_info: &BootInfo) {
         .retype_single_with_fixed_size::<Page>(&mut root_cnode.get_slot().unwrap())
         .unwrap();
 
-    let flags = PageFlags::readonly();
+    let flags = PageFlags::readandwrite();
     page.map(&mut root_vspace, vaddr, flags).unwrap();
-    page.unmap(&mut root_vspace).unwrap();
+    page.remap(PageFlags::readonly()).unwrap();
+    page.unmap().unwrap();
 
+    // new process has its own asid pool
+    let mut asid_control = get_asid_control(boot_info);
+    let mut asid_pool = asid_control
+        .make_pool(&mut untyped, &mut root_cnode.get_slot().unwrap())
+        .unwrap();
     let mut elf_mapper =
-        ElfProgramMapper::try_new(lv2_cnode, untyped, &mut root_vspace, vaddr).unwrap();
+        ElfProgramMapper::try_new(lv2_cnode, untyped, &mut root_vspace, &mut asid_pool, vaddr)
+            .unwrap();
 
     let new_proc_elf = new_proc_elf();
     let new_entry = new_proc_elf.e_entry;
@@ -96,14 +124,19 @@ pub fn main(boot_info: &BootInfo) {
     let _copied_notiry = lv2_cnode.copy::<Notificaiton>(&notify).unwrap();
     let minted_not_1 = root_cnode.mint(&notify, 0b100).unwrap();
     let minted_not_2 = root_cnode.mint(&notify, 0b1000).unwrap();
-    let minted_ep = root_cnode.mint(&endpoint, 0xdeadbeef).unwrap();
+    let mut minted_ep = root_cnode.mint(&endpoint, 0xdeadbeef).unwrap();
+    let mut minted_ep_2 = root_cnode.mint(&endpoint, 0xcafe).unwrap();
     child_tcb
         .configure(&mut root_cnode, &mut root_vspace)
         .unwrap();
+    // badge tells which child stopped.
+    let child_death = root_cnode.mint(&death, 0b1).unwrap();
+    child_tcb.bind_death_notification(&child_death).unwrap();
 
-    let sp_val = unsafe {
+    // child has its own thread local storage at top of its stack.
+    let (tls_base, sp_val) = unsafe {
         let stack_bottom = &mut STACK[511];
-        stack_bottom as *mut usize as usize
+        init_tls_on_stack(stack_bottom as *mut usize as usize)
     };
     child_tcb
         .write_regs(
@@ -119,7 +152,18 @@ pub fn main(boot_info: &BootInfo) {
             boot_info.ipc_buffer(),
         )
         .unwrap();
+    child_tcb.set_tls_base(tls_base).unwrap();
+    #[cfg(feature = "debug-syscalls")]
+    child_tcb.set_name("child").unwrap();
+    println!("child tid is {}", child_tcb.get_id().unwrap());
+    THREAD_LOCAL_CHECK.set(0);
+    let regs = child_tcb.read_regs(boot_info.ipc_buffer()).unwrap();
+    assert_eq!(regs.sepc, children as usize);
 
+    // child sends through the slot given above, which holds the other badge after swap.
+    root_cnode.swap(&mut minted_ep, &mut minted_ep_2).unwrap();
+    assert_eq!(minted_ep_2.cap_ptr, regs.a2);
+    #[cfg(feature = "debug-syscalls")]
     traverse().unwrap();
     child_tcb.resume().unwrap();
     println!("parnet: wait");
@@ -130,11 +174,33 @@ pub fn main(boot_info: &BootInfo) {
     endpoint.send().unwrap();
     println!("parnet: send done");
     println!("parent: call recv");
-    endpoint.recive().unwrap();
+    assert_eq!(endpoint.recive().unwrap(), 0xcafe);
     println!("parnet: recv done");
     println!("parent: call recv");
-    endpoint.recive().unwrap();
+    assert_eq!(endpoint.recive().unwrap(), 0xcafe);
     println!("parnet: recv done");
+    // endpoint is not a notification, so kernel tells which argument is wrong.
+    let err = bind_death_notification(
+        child_tcb.cap_ptr,
+        child_tcb.cap_depth,
+        endpoint.cap_ptr,
+        endpoint.cap_depth,
+    )
+    .detail(boot_info.ipc_buffer())
+    .unwrap_err();
+    println!("parent: expected error, {err}");
+    let badge = child_tcb.join(&death).unwrap();
+    assert_eq!(badge, 0b1);
+    println!("parent: child exited");
+    check_cnode_rotate(&mut root_cnode, &death);
+    let idle_time = get_sched_control(boot_info)
+        .get_idle_time(boot_info.boot_hartid, boot_info.ipc_buffer())
+        .unwrap();
+    println!("parent: {idle_time:?}");
+    let null_syscall = measure(100, rdcycle, || child_tcb.get_id().map(|_| ())).unwrap();
+    println!("parent: null syscall {null_syscall:?}");
+    print_instret(boot_info);
+    print_trace(boot_info, &mut root_vspace, 8);
     new_proc
         .configure(&mut lv2_cnode, &mut root_vspace_for_new_proc)
         .unwrap();
@@ -148,9 +214,67 @@ pub fn main(boot_info: &BootInfo) {
         )
         .unwrap();
     new_proc.resume().unwrap();
+    #[cfg(feature = "debug-syscalls")]
+    libzoea::syscall::debug_dump_scheduler().unwrap();
     panic!("iam parent");
 }
 
+/// Rotate minted notifications, and check badges follow the caps.
+fn check_cnode_rotate(root_cnode: &mut CNodeCapability, notify: &NotificaitonCapability) {
+    let mut src = root_cnode.mint(notify, 0b10000).unwrap();
+    let mut pivot = root_cnode.mint(notify, 0b100000).unwrap();
+    let src_slot = NotificaitonCapability {
+        cap_ptr: src.cap_ptr,
+        cap_depth: src.cap_depth,
+        cap_data: Notificaiton {},
+    };
+    root_cnode.rotate(&mut src, &mut pivot).unwrap();
+    src.send().unwrap();
+    assert_eq!(notify.wait().unwrap(), 0b10000);
+    pivot.send().unwrap();
+    assert_eq!(notify.wait().unwrap(), 0b100000);
+    // src was moved out of its slot.
+    src_slot.send().unwrap_err();
+    println!("parent: rotate done");
+}
+
+/// Count retired instructions of println through PMU, if SBI supports it.
+fn print_instret(boot_info: &BootInfo) {
+    // SBI_PMU_HW_INSTRUCTIONS
+    const HW_INSTRUCTIONS: usize = 2;
+    let benchmark = get_benchmark(boot_info);
+    let Ok(counter_idx) = benchmark.config_counter(HW_INSTRUCTIONS, 0) else {
+        println!("parent: pmu is not available");
+        return;
+    };
+    benchmark.start_counter(counter_idx, 0).unwrap();
+    println!("parent: counting instructions");
+    benchmark.stop_counter(counter_idx).unwrap();
+    let value = benchmark
+        .read_counter(counter_idx, boot_info.ipc_buffer())
+        .unwrap();
+    println!("parent: {value:?}");
+    benchmark.release_counter(counter_idx).unwrap();
+}
+
+/// Map kernel trace buffer and print the latest events.
+fn print_trace(boot_info: &BootInfo, root_vspace: &mut PageTableCapability, num: usize) {
+    if boot_info.trace_buffer_pages == 0 {
+        return;
+    }
+    // below the pages used by main.
+    let vaddr = 0x0000000001000000 - 0x10000;
+    for (i, mut page) in get_trace_buffer_pages(boot_info).enumerate() {
+        page.map(root_vspace, vaddr + i * 0x1000, PageFlags::readonly())
+            .unwrap();
+    }
+    let trace_buffer = unsafe { &*(vaddr as *const TraceBuffer) };
+    let len = (trace_buffer.head as usize).min(TRACE_BUFFER_LEN);
+    for event in trace_buffer.events().skip(len.saturating_sub(num)) {
+        println!("trace: {event}");
+    }
+}
+
 #[allow(clippy::empty_loop)]
 fn children(not_cptr: usize, not_depth: u32, ep_ptr: usize, ep_depth: u32) {
     let not = NotificaitonCapability {
@@ -163,6 +287,8 @@ fn children(not_cptr: usize, not_depth: u32, ep_ptr: usize, ep_depth: u32) {
         cap_depth: ep_depth,
         cap_data: Endpoint {},
     };
+    // parent's change is not visible.
+    assert_eq!(THREAD_LOCAL_CHECK.get(), 0x1234);
     println!("children: notification is {not:?}");
     println!("children: ep is {ep:?}");
     not.send().unwrap();
@@ -176,5 +302,5 @@ fn children(not_cptr: usize, not_depth: u32, ep_ptr: usize, ep_depth: u32) {
     println!("child: call send");
     ep.send().unwrap();
     println!("child: send done");
-    panic!("iam child");
+    exit();
 }
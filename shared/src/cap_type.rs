@@ -1,30 +1,23 @@
-use crate::err_kind::ErrKind;
+use abi_enum::abi_enum;
 
-#[repr(u8)]
-#[derive(Debug, PartialEq, Eq)]
-pub enum CapabilityType {
-    Untyped = 1,
-    Tcb = 3,
-    EndPoint = 5,
-    CNode = 7,
-    Notification = 9,
-    // Arch
-    PageTable = 2,
-    Page = 4,
-}
+use crate::err_kind::ErrKind;
 
-impl TryFrom<u8> for CapabilityType {
-    type Error = ErrKind;
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            x if x == Self::Untyped as u8 => Ok(Self::Untyped),
-            x if x == Self::Tcb as u8 => Ok(Self::Tcb),
-            x if x == Self::EndPoint as u8 => Ok(Self::EndPoint),
-            x if x == Self::CNode as u8 => Ok(Self::CNode),
-            x if x == Self::Notification as u8 => Ok(Self::Notification),
-            x if x == Self::Page as u8 => Ok(Self::Page),
-            x if x == Self::PageTable as u8 => Ok(Self::PageTable),
-            _ => Err(ErrKind::UnknownCapType),
-        }
+abi_enum! {
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CapabilityType {
+        Untyped = 1,
+        Tcb = 3,
+        EndPoint = 5,
+        CNode = 7,
+        Notification = 9,
+        SchedControl = 11,
+        // Arch
+        PageTable = 2,
+        Page = 4,
+        AsidControl = 6,
+        AsidPool = 8,
+        Benchmark = 10,
     }
+    impl TryFrom<u8, Error = ErrKind> => ErrKind::UnknownCapType;
 }
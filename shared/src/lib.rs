@@ -5,8 +5,10 @@ pub mod cap_type;
 pub mod elf;
 pub mod err_kind;
 pub mod inv_labels;
+pub mod page_size;
 pub mod registers;
 pub mod syscall_no;
+pub mod trace;
 pub mod types;
 
 pub const PAGE_SIZE: usize = 4096;
@@ -0,0 +1,48 @@
+use crate::err_kind::ErrKind;
+
+/// Size of page frame.
+/// Value is the level of page table where the frame is mapped as leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum PageSize {
+    #[default]
+    Small = 0, // 4KiB
+    Mega = 1, // 2MiB
+    Giga = 2, // 1GiB
+}
+
+impl PageSize {
+    pub const fn bytes(self) -> usize {
+        1 << self.bits()
+    }
+
+    pub const fn bits(self) -> usize {
+        12 + 9 * (self as usize)
+    }
+
+    pub const fn level(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_level(level: usize) -> Option<Self> {
+        match level {
+            0 => Some(Self::Small),
+            1 => Some(Self::Mega),
+            2 => Some(Self::Giga),
+            _ => None,
+        }
+    }
+}
+
+/// From size in bytes.
+impl TryFrom<usize> for PageSize {
+    type Error = ErrKind;
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            x if x == Self::Small.bytes() => Ok(Self::Small),
+            x if x == Self::Mega.bytes() => Ok(Self::Mega),
+            x if x == Self::Giga.bytes() => Ok(Self::Giga),
+            _ => Err(ErrKind::InvalidUserSize),
+        }
+    }
+}
@@ -203,3 +203,20 @@ impl IndexMut<Register> for Registers {
         }
     }
 }
+
+/// Floating-point registers (f0-f31) and fcsr.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FpRegisters {
+    pub f: [u64; 32],
+    pub fcsr: usize,
+}
+
+impl FpRegisters {
+    pub const fn null() -> Self {
+        Self {
+            f: [0; 32],
+            fcsr: 0,
+        }
+    }
+}
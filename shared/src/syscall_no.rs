@@ -1,7 +1,12 @@
 #[repr(u8)]
 pub enum SysCallNo {
+    /// Console output for user, kernel supports it only with debug-syscalls feature.
     Print = 0,
     Call = 1,
     Send = 2,
     Recv = 3,
+    /// Stop calling thread.
+    Exit = 4,
+    /// Debug interface, kernel supports it only with debug-syscalls feature.
+    Debug = 5,
 }
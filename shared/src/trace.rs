@@ -0,0 +1,235 @@
+use core::fmt;
+use core::mem;
+
+use crate::cap_type::CapabilityType;
+use crate::const_assert;
+use crate::err_kind::ErrKind;
+use crate::inv_labels::InvLabel;
+use crate::PAGE_SIZE;
+
+/*
+ * Layout of kernel trace buffer, which is mapped read only to user space.
+ * | head 8 | event 0 32 | event 1 32 | ... | event TRACE_BUFFER_LEN-1 32 |
+ * head is the number of events written since boot,
+ * and event i is at events[i % TRACE_BUFFER_LEN].
+ */
+pub const TRACE_BUFFER_PAGES: usize = 8;
+pub const TRACE_BUFFER_LEN: usize =
+    (TRACE_BUFFER_PAGES * PAGE_SIZE - mem::size_of::<u64>()) / mem::size_of::<RawTraceEvent>();
+
+const TRACE_TRAP: u64 = 1;
+const TRACE_SYSCALL: u64 = 2;
+const TRACE_CONTEXT_SWITCH: u64 = 3;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawTraceEvent {
+    /// Value of time csr.
+    pub timestamp: u64,
+    pub kind: u64,
+    pub arg0: u64,
+    pub arg1: u64,
+}
+
+impl RawTraceEvent {
+    pub fn trap(timestamp: usize, scause: usize, sepc: usize) -> Self {
+        Self {
+            timestamp: timestamp as u64,
+            kind: TRACE_TRAP,
+            arg0: scause as u64,
+            arg1: sepc as u64,
+        }
+    }
+
+    /// arg0 is | err kind 8 | cap type 8 | syscall number 8 |, 0 means none for the first two.
+    pub fn syscall(
+        timestamp: usize,
+        syscall_n: usize,
+        inv_label: usize,
+        cap_type: Option<CapabilityType>,
+        err: Option<ErrKind>,
+    ) -> Self {
+        let cap_type = cap_type.map_or(0, |cap_type| cap_type as u64);
+        let err = err.map_or(0, |e_kind| e_kind as u64);
+        Self {
+            timestamp: timestamp as u64,
+            kind: TRACE_SYSCALL,
+            arg0: (syscall_n as u64 & 0xff) | (cap_type << 8) | (err << 16),
+            arg1: inv_label as u64,
+        }
+    }
+
+    pub fn context_switch(timestamp: usize, from_tid: usize, to_tid: usize) -> Self {
+        Self {
+            timestamp: timestamp as u64,
+            kind: TRACE_CONTEXT_SWITCH,
+            arg0: from_tid as u64,
+            arg1: to_tid as u64,
+        }
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        let field = |i: usize| {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(&bytes[i * 8..(i + 1) * 8]);
+            u64::from_le_bytes(buf)
+        };
+        Self {
+            timestamp: field(0),
+            kind: field(1),
+            arg0: field(2),
+            arg1: field(3),
+        }
+    }
+}
+
+#[repr(C)]
+pub struct TraceBuffer {
+    pub head: u64,
+    pub events: [RawTraceEvent; TRACE_BUFFER_LEN],
+}
+
+const_assert!(mem::size_of::<TraceBuffer>() <= TRACE_BUFFER_PAGES * PAGE_SIZE);
+
+impl TraceBuffer {
+    pub const fn new() -> Self {
+        Self {
+            head: 0,
+            events: [RawTraceEvent {
+                timestamp: 0,
+                kind: 0,
+                arg0: 0,
+                arg1: 0,
+            }; TRACE_BUFFER_LEN],
+        }
+    }
+
+    /// Overwrite the oldest event if buffer is full.
+    pub fn record(&mut self, event: RawTraceEvent) {
+        self.events[self.head as usize % TRACE_BUFFER_LEN] = event;
+        self.head += 1;
+    }
+
+    /// Events in the buffer from oldest to newest.
+    pub fn events(&self) -> impl Iterator<Item = TraceEvent> + '_ {
+        let head = self.head as usize;
+        (head.saturating_sub(TRACE_BUFFER_LEN)..head)
+            .map(|i| TraceEvent::from(self.events[i % TRACE_BUFFER_LEN]))
+    }
+}
+
+impl Default for TraceBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode dump of trace buffer, for example one read on host through gdb.
+/// Events are returned from oldest to newest, and broken tail is ignored.
+pub fn decode(bytes: &[u8]) -> impl Iterator<Item = TraceEvent> + '_ {
+    let event_size = mem::size_of::<RawTraceEvent>();
+    let head = bytes.get(..8).map_or(0, |head| {
+        u64::from_le_bytes(head.try_into().unwrap()) as usize
+    });
+    let events = bytes.get(8..).unwrap_or_default();
+    (head.saturating_sub(TRACE_BUFFER_LEN)..head).filter_map(move |i| {
+        let offset = (i % TRACE_BUFFER_LEN) * event_size;
+        events
+            .get(offset..offset + event_size)
+            .map(|bytes| TraceEvent::from(RawTraceEvent::from_le_bytes(bytes)))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub timestamp: u64,
+    pub record: TraceRecord,
+}
+
+/// Error of traced syscall, kernel newer than the decoder may record unknown kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceErr {
+    Kind(ErrKind),
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceRecord {
+    Trap {
+        scause: usize,
+        sepc: usize,
+    },
+    SysCall {
+        syscall_n: usize,
+        inv_label: Option<InvLabel>,
+        cap_type: Option<CapabilityType>,
+        result: Result<(), TraceErr>,
+    },
+    ContextSwitch {
+        from_tid: usize,
+        to_tid: usize,
+    },
+    Unknown {
+        kind: u64,
+    },
+}
+
+impl From<RawTraceEvent> for TraceEvent {
+    fn from(raw: RawTraceEvent) -> Self {
+        let record = match raw.kind {
+            TRACE_TRAP => TraceRecord::Trap {
+                scause: raw.arg0 as usize,
+                sepc: raw.arg1 as usize,
+            },
+            TRACE_SYSCALL => {
+                let err = ((raw.arg0 >> 16) & 0xff) as u8;
+                TraceRecord::SysCall {
+                    syscall_n: (raw.arg0 & 0xff) as usize,
+                    inv_label: InvLabel::try_from(raw.arg1 as usize).ok(),
+                    cap_type: CapabilityType::try_from((raw.arg0 >> 8) as u8).ok(),
+                    result: match err {
+                        0 => Ok(()),
+                        e_val => Err(ErrKind::try_from(e_val as usize)
+                            .map_or(TraceErr::Unknown(e_val), TraceErr::Kind)),
+                    },
+                }
+            }
+            TRACE_CONTEXT_SWITCH => TraceRecord::ContextSwitch {
+                from_tid: raw.arg0 as usize,
+                to_tid: raw.arg1 as usize,
+            },
+            kind => TraceRecord::Unknown { kind },
+        };
+        Self {
+            timestamp: raw.timestamp,
+            record,
+        }
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>12}] ", self.timestamp)?;
+        match self.record {
+            TraceRecord::Trap { scause, sepc } => {
+                write!(f, "trap scause={:#x} sepc={:#x}", scause, sepc)
+            }
+            TraceRecord::SysCall {
+                syscall_n,
+                inv_label,
+                cap_type,
+                result,
+            } => {
+                write!(f, "syscall {} {:?}", syscall_n, inv_label)?;
+                if let Some(cap_type) = cap_type {
+                    write!(f, " on {:?}", cap_type)?;
+                }
+                write!(f, " -> {:?}", result)
+            }
+            TraceRecord::ContextSwitch { from_tid, to_tid } => {
+                write!(f, "switch tid {} -> {}", from_tid, to_tid)
+            }
+            TraceRecord::Unknown { kind } => write!(f, "unknown event kind={}", kind),
+        }
+    }
+}
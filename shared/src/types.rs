@@ -7,6 +7,8 @@ pub struct IPCBuffer {
     pub tag: usize,
     pub message: [usize; MESSAGE_LEN],
     pub user_data: usize,
+    /// Written by kernel when a syscall of the owner thread fails.
+    pub err_record: ErrRecord,
 }
 
 impl IPCBuffer {
@@ -33,6 +35,99 @@ impl IPCBuffer {
         unsafe { Ok(ptr.as_ref().unwrap()) }
     }
 }
+/// Field of ErrRecord which doesn't apply to the error.
+pub const ERR_RECORD_NONE: usize = usize::MAX;
+pub const ERR_RECORD_FILE_LEN: usize = 32;
+
+/// Detail of failed syscall, fields are ERR_RECORD_NONE if they are unknown.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrRecord {
+    pub e_kind: usize,
+    pub e_val: usize,
+    /// Index of argument register which is invalid, 0 is a0.
+    pub invalid_arg: usize,
+    /// Number of bits resolved before capability lookup failed.
+    pub failed_depth: usize,
+    /// Number of bits left when capability lookup failed.
+    pub bits_left: usize,
+    /// Capability types as CapabilityType.
+    pub expected_cap_type: usize,
+    pub actual_cap_type: usize,
+    /// Kernel source which returned the error, only in debug build of kernel.
+    /// file is the tail of path if it is longer than ERR_RECORD_FILE_LEN.
+    pub line: usize,
+    pub file_len: usize,
+    pub file: [u8; ERR_RECORD_FILE_LEN],
+}
+
+impl ErrRecord {
+    pub const fn new(e_kind: ErrKind, e_val: usize) -> Self {
+        Self {
+            e_kind: e_kind as usize,
+            e_val,
+            invalid_arg: ERR_RECORD_NONE,
+            failed_depth: ERR_RECORD_NONE,
+            bits_left: ERR_RECORD_NONE,
+            expected_cap_type: ERR_RECORD_NONE,
+            actual_cap_type: ERR_RECORD_NONE,
+            line: ERR_RECORD_NONE,
+            file_len: 0,
+            file: [0; ERR_RECORD_FILE_LEN],
+        }
+    }
+
+    pub fn set_place(&mut self, file: &str, line: u32) {
+        let file = file.as_bytes();
+        let file = &file[file.len().saturating_sub(ERR_RECORD_FILE_LEN)..];
+        self.file[..file.len()].copy_from_slice(file);
+        self.file_len = file.len();
+        self.line = line as usize;
+    }
+}
+
+/// Mint value of CNode cap is | guard | guard size 6 |,
+/// and guard size bits of cap ptr must be equal to guard before radix bits are resolved.
+pub const CNODE_GUARD_SIZE_BITS: u32 = 6;
+
+pub fn cnode_guard(guard: usize, guard_size: u32) -> usize {
+    (guard << CNODE_GUARD_SIZE_BITS) | guard_size as usize
+}
+
+/// (guard, guard size) of mint value.
+pub fn cnode_guard_from_value(value: usize) -> (usize, u32) {
+    let guard_size = (value & ((1 << CNODE_GUARD_SIZE_BITS) - 1)) as u32;
+    (value >> CNODE_GUARD_SIZE_BITS, guard_size)
+}
+
+/// Options of TcbCopyRegisters.
+pub const COPY_REGS_SUSPEND_SOURCE: usize = 1 << 0;
+pub const COPY_REGS_RESUME_TARGET: usize = 1 << 1;
+pub const COPY_REGS_FP: usize = 1 << 2;
+
+/// Result of BenchmarkReadCounter.
+/// overflows is the number of overflow interrupts since the counter was started.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct CounterValue {
+    pub value: usize,
+    pub overflows: usize,
+}
+
+/// Result of SchedControlGetIdleTime, both are counted in ticks of time csr.
+/// Utilisation of the hart is 1 - idle_time / up_time.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct IdleTime {
+    pub idle_time: usize,
+    pub up_time: usize,
+}
+
+/// Result of UntypedRetype returned in a1 and a2, free_offset is in bytes from start of the untyped.
+/// num may be less than requested, because kernel zero clears limited size of RAM per call.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct RetypeResult {
+    pub num: usize,
+    pub free_offset: usize,
+}
+
 // bits, idx, is_device
 #[derive(Default, Debug)]
 pub struct UntypedInfo {
@@ -46,6 +141,15 @@ pub struct BootInfo {
     pub ipc_buffer_addr: usize,
     pub root_cnode_idx: usize,
     pub root_vspace_idx: usize,
+    pub asid_control_idx: usize,
+    pub asid_pool_idx: usize,
+    pub sched_control_idx: usize,
+    pub benchmark_idx: usize,
+    /// Hart which root server starts on.
+    pub boot_hartid: usize,
+    /// Read only page caps of kernel trace buffer, pages are 0 if kernel is built without trace.
+    pub trace_buffer_idx: usize,
+    pub trace_buffer_pages: usize,
     pub untyped_num: usize,
     pub firtst_empty_idx: usize,
     pub msg: [u8; 32],
@@ -0,0 +1,141 @@
+use shared::cap_type::CapabilityType;
+use shared::err_kind::ErrKind;
+use shared::inv_labels::InvLabel;
+
+#[test]
+fn err_kind_round_trip() {
+    for &e_kind in ErrKind::VARIANTS {
+        assert_eq!(ErrKind::try_from(e_kind as usize), Ok(e_kind));
+    }
+    assert_eq!(ErrKind::try_from(0), Err(()));
+    assert_eq!(ErrKind::try_from(ErrKind::VARIANTS.len() + 1), Err(()));
+}
+
+#[test]
+fn inv_label_round_trip() {
+    for &inv_label in InvLabel::VARIANTS {
+        assert_eq!(InvLabel::try_from(inv_label as usize), Ok(inv_label));
+    }
+    assert_eq!(
+        InvLabel::try_from(InvLabel::VARIANTS.len()),
+        Err(ErrKind::UnknownInvocation)
+    );
+}
+
+#[test]
+fn cap_type_round_trip() {
+    for &cap_type in CapabilityType::VARIANTS {
+        assert_eq!(CapabilityType::try_from(cap_type as u8), Ok(cap_type));
+    }
+    assert_eq!(CapabilityType::try_from(0), Err(ErrKind::UnknownCapType));
+}
+
+// values are ABI shared with built user programs, so variants must not be reordered.
+#[test]
+fn err_kind_values() {
+    let expected = [
+        (ErrKind::NoMemory, 1),
+        (ErrKind::PteNotFound, 2),
+        (ErrKind::OutOfMemory, 3),
+        (ErrKind::InvalidUserAddress, 4),
+        (ErrKind::UnknownCapType, 5),
+        (ErrKind::UnexpectedCapType, 6),
+        (ErrKind::CanNotNewFromDeviceMemory, 7),
+        (ErrKind::NoEnoughSlot, 8),
+        (ErrKind::NotEmptySlot, 9),
+        (ErrKind::SlotIsEmpty, 10),
+        (ErrKind::VaddressAlreadyMapped, 11),
+        (ErrKind::PageTableAlreadyMapped, 12),
+        (ErrKind::PageTableNotMappedYet, 13),
+        (ErrKind::PageAlreadyMapped, 14),
+        (ErrKind::PageNotMappedYet, 15),
+        (ErrKind::UnknownInvocation, 16),
+        (ErrKind::CanNotDerivable, 17),
+        (ErrKind::InvalidOperation, 18),
+        (ErrKind::CapNotFound, 19),
+        (ErrKind::NotAligned, 20),
+        (ErrKind::UnknownSysCall, 21),
+        (ErrKind::NotRootPageTable, 22),
+        (ErrKind::InvalidUserSize, 23),
+        (ErrKind::MissingCapability, 24),
+        (ErrKind::DepthMismatch, 25),
+        (ErrKind::GuardMismatch, 26),
+        (ErrKind::IpcCancelled, 27),
+    ];
+    assert_eq!(expected.len(), ErrKind::VARIANTS.len());
+    for (e_kind, value) in expected {
+        assert_eq!(e_kind as usize, value);
+    }
+}
+
+#[test]
+fn inv_label_values() {
+    let expected = [
+        (InvLabel::PutChar, 0),
+        (InvLabel::CNodeTraverse, 1),
+        (InvLabel::UntypedRetype, 2),
+        (InvLabel::TcbConfigure, 3),
+        (InvLabel::TcbWriteReg, 4),
+        (InvLabel::TcbResume, 5),
+        (InvLabel::TcbSetIpcBuffer, 6),
+        (InvLabel::NotifyWait, 7),
+        (InvLabel::NotifySend, 8),
+        (InvLabel::CNodeCopy, 9),
+        (InvLabel::CNodeMint, 10),
+        (InvLabel::CNodeMove, 11),
+        (InvLabel::PageMap, 12),
+        (InvLabel::PageUnMap, 13),
+        (InvLabel::PageTableMap, 14),
+        (InvLabel::PageTableUnMap, 15),
+        (InvLabel::PageTableMakeRoot, 16),
+        (InvLabel::EpSend, 17),
+        (InvLabel::EpRecv, 18),
+        (InvLabel::AsidControlMakePool, 19),
+        (InvLabel::PageRemap, 20),
+        (InvLabel::PageGetAddress, 21),
+        (InvLabel::TcbReadRegisters, 22),
+        (InvLabel::TcbCopyRegisters, 23),
+        (InvLabel::TcbSetTLSBase, 24),
+        (InvLabel::TcbGetId, 25),
+        (InvLabel::TcbSuspend, 26),
+        (InvLabel::TcbBindDeathNotification, 27),
+        (InvLabel::SchedControlGetIdleTime, 28),
+        (InvLabel::DebugPutString, 29),
+        (InvLabel::DebugHalt, 30),
+        (InvLabel::DebugDumpScheduler, 31),
+        (InvLabel::DebugNameThread, 32),
+        (InvLabel::DebugCapIdentify, 33),
+        (InvLabel::BenchmarkConfigCounter, 34),
+        (InvLabel::BenchmarkStartCounter, 35),
+        (InvLabel::BenchmarkStopCounter, 36),
+        (InvLabel::BenchmarkReadCounter, 37),
+        (InvLabel::BenchmarkReleaseCounter, 38),
+        (InvLabel::CNodeSwap, 39),
+        (InvLabel::CNodeRotate, 40),
+    ];
+    assert_eq!(expected.len(), InvLabel::VARIANTS.len());
+    for (inv_label, value) in expected {
+        assert_eq!(inv_label as usize, value);
+    }
+}
+
+#[test]
+fn cap_type_values() {
+    let expected = [
+        (CapabilityType::Untyped, 1),
+        (CapabilityType::Tcb, 3),
+        (CapabilityType::EndPoint, 5),
+        (CapabilityType::CNode, 7),
+        (CapabilityType::Notification, 9),
+        (CapabilityType::SchedControl, 11),
+        (CapabilityType::PageTable, 2),
+        (CapabilityType::Page, 4),
+        (CapabilityType::AsidControl, 6),
+        (CapabilityType::AsidPool, 8),
+        (CapabilityType::Benchmark, 10),
+    ];
+    assert_eq!(expected.len(), CapabilityType::VARIANTS.len());
+    for (cap_type, value) in expected {
+        assert_eq!(cap_type as u8, value);
+    }
+}
@@ -0,0 +1,116 @@
+use shared::cap_type::CapabilityType;
+use shared::err_kind::ErrKind;
+use shared::inv_labels::InvLabel;
+use shared::trace::{
+    decode, RawTraceEvent, TraceBuffer, TraceErr, TraceEvent, TraceRecord, TRACE_BUFFER_LEN,
+};
+
+/// Same layout as the buffer which is read through gdb.
+fn dump(buffer: &TraceBuffer) -> Vec<u8> {
+    let mut bytes = buffer.head.to_le_bytes().to_vec();
+    for event in buffer.events.iter() {
+        for field in [event.timestamp, event.kind, event.arg0, event.arg1] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn switch_tids(events: impl Iterator<Item = TraceEvent>) -> Vec<usize> {
+    events
+        .map(|event| match event.record {
+            TraceRecord::ContextSwitch { from_tid, .. } => from_tid,
+            record => panic!("unexpected record {:?}", record),
+        })
+        .collect()
+}
+
+#[test]
+fn decode_dump() {
+    let mut buffer = Box::new(TraceBuffer::new());
+    buffer.record(RawTraceEvent::trap(1, 5, 0x1000));
+    buffer.record(RawTraceEvent::syscall(
+        2,
+        3,
+        InvLabel::TcbResume as usize,
+        Some(CapabilityType::Tcb),
+        None,
+    ));
+    buffer.record(RawTraceEvent::syscall(
+        3,
+        3,
+        InvLabel::CNodeCopy as usize,
+        Some(CapabilityType::CNode),
+        Some(ErrKind::SlotIsEmpty),
+    ));
+    buffer.record(RawTraceEvent::context_switch(4, 1, 2));
+
+    let decoded: Vec<_> = decode(&dump(&buffer)).collect();
+    assert_eq!(decoded, buffer.events().collect::<Vec<_>>());
+    let records: Vec<_> = decoded.iter().map(|event| event.record).collect();
+    assert_eq!(
+        records,
+        [
+            TraceRecord::Trap {
+                scause: 5,
+                sepc: 0x1000
+            },
+            TraceRecord::SysCall {
+                syscall_n: 3,
+                inv_label: Some(InvLabel::TcbResume),
+                cap_type: Some(CapabilityType::Tcb),
+                result: Ok(()),
+            },
+            TraceRecord::SysCall {
+                syscall_n: 3,
+                inv_label: Some(InvLabel::CNodeCopy),
+                cap_type: Some(CapabilityType::CNode),
+                result: Err(TraceErr::Kind(ErrKind::SlotIsEmpty)),
+            },
+            TraceRecord::ContextSwitch {
+                from_tid: 1,
+                to_tid: 2
+            },
+        ]
+    );
+}
+
+#[test]
+fn decode_wrapped_dump() {
+    let mut buffer = Box::new(TraceBuffer::new());
+    let total = TRACE_BUFFER_LEN + 5;
+    for tid in 0..total {
+        buffer.record(RawTraceEvent::context_switch(tid, tid, tid + 1));
+    }
+
+    let expected: Vec<_> = (5..total).collect();
+    assert_eq!(switch_tids(buffer.events()), expected);
+    assert_eq!(switch_tids(decode(&dump(&buffer))), expected);
+}
+
+#[test]
+fn decode_broken_tail() {
+    let mut buffer = Box::new(TraceBuffer::new());
+    for tid in 0..3 {
+        buffer.record(RawTraceEvent::context_switch(tid, tid, tid + 1));
+    }
+    let bytes = dump(&buffer);
+    // only the first two events and half of the third one are left.
+    let truncated = &bytes[..8 + 32 * 2 + 16];
+    assert_eq!(switch_tids(decode(truncated)), [0, 1]);
+    assert_eq!(decode(&bytes[..4]).count(), 0);
+}
+
+#[test]
+fn decode_unknown_err_kind() {
+    let mut event = RawTraceEvent::syscall(1, 3, InvLabel::CNodeCopy as usize, None, None);
+    event.arg0 |= 0xfe << 16;
+    let record = TraceEvent::from(event).record;
+    assert!(matches!(
+        record,
+        TraceRecord::SysCall {
+            result: Err(TraceErr::Unknown(0xfe)),
+            ..
+        }
+    ));
+}
@@ -5,6 +5,7 @@
 use core::{arch::naked_asm, panic::PanicInfo};
 
 use libzoea::println;
+use libzoea::tls::init_main_thread;
 
 mod simple;
 
@@ -19,9 +20,11 @@ extern "C" fn start() {
     unsafe {
         naked_asm!(
         "la sp, {stack_top}",
+        "call {init_tls}",
         "call main",
         "call exit",
-        stack_top = sym __stack_top
+        stack_top = sym __stack_top,
+        init_tls = sym init_main_thread,
         )
     }
 }
@@ -34,5 +37,5 @@ fn panic(info: &PanicInfo) -> ! {
 
 #[no_mangle]
 fn exit() -> ! {
-    panic!("exit")
+    libzoea::syscall::exit()
 }